@@ -1,4 +1,5 @@
 extern crate sequencefile;
+extern crate sequencefile_derive;
 use std::{
     collections::HashMap,
     fs::File,
@@ -8,48 +9,21 @@ use std::{
 use sequencefile::{writable::Writable, Reader, Text};
 
 #[allow(dead_code)]
+#[derive(sequencefile_derive::Writable)]
 struct Simple {
     s1: i64,
 }
 
-impl Writable for Simple {
-    fn read(buf: &mut impl std::io::Read) -> sequencefile::Result<Self>
-    where
-        Self: Sized,
-    {
-        Ok(Self {
-            s1: i64::read(buf)?,
-        })
-    }
-}
-
 #[allow(dead_code)]
+#[derive(sequencefile_derive::Writable)]
 struct Complex {
     s1: i64,
     s2: u8,
     s3: i16,
+    #[writable(map, text)]
     s4: HashMap<String, String>,
 }
 
-impl Writable for Complex {
-    fn read(buf: &mut impl std::io::Read) -> sequencefile::Result<Self>
-    where
-        Self: Sized,
-    {
-        let s1 = i64::read(buf)?;
-        let s2 = u8::read(buf)?;
-        let s3 = i16::read(buf)?;
-        let len = i32::read(buf)?;
-        let mut s4 = HashMap::<String, String>::new();
-        for _ in 0..len {
-            let key = Text::read(buf)?;
-            let val = Text::read(buf)?;
-            s4.insert(key.to_string().to_string(), val.to_string().to_string());
-        }
-        Ok(Self { s1, s2, s3, s4 })
-    }
-}
-
 fn read_from_file<K: Writable, V: Writable>(path: &str) {
     let file = File::open(path).expect("cannot open input file");
     let reader = Reader::<File, K, V>::new(file).expect("cannot open reader");
@@ -0,0 +1,264 @@
+//! `#[derive(Writable)]` for the sequencefile crate
+//!
+//! Generates `Writable::read` and `Writable::write` implementations which (de)serialize
+//! fields in declaration order, the same way a hand-written Hadoop `Writable` would.
+//!
+//! # Field attributes
+//! - `#[writable(skip)]` - field is not serialized, `Default::default()` is used on read
+//! - `#[writable(vint)]` - `i32`/`i64` written as a zero-compressed vint
+//! - `#[writable(text)]` - `String` written as a hadoop.io.Text
+//! - `#[writable(seq)]` - collection written as an i32 length followed by the elements
+//! - `#[writable(map)]` - map written as an i32 length followed by keys and values
+//!
+//! `vint` and `text` may be combined with `seq`/`map` to set the element encoding, e.g.
+//! `#[writable(map, text)]` for a `HashMap<String, String>`. Maps may set key and value
+//! encodings separately with `key = "text"` and `value = "vint"`.
+//!
+//! # Example
+//! ```
+//! # use sequencefile::writable::Writable;
+//! # use std::collections::HashMap;
+//! #[derive(Writable)]
+//! struct Complex {
+//!     s1: i64,
+//!     s2: u8,
+//!     s3: i16,
+//!     #[writable(map, text)]
+//!     s4: HashMap<String, String>,
+//! }
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Field, Fields, GenericParam, LitStr,
+    Result,
+};
+
+/// Derives `sequencefile::writable::Writable` for a struct
+#[proc_macro_derive(Writable, attributes(writable))]
+pub fn derive_writable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Writable,
+    VInt,
+    Text,
+}
+
+impl Encoding {
+    fn parse(lit: &LitStr) -> Result<Encoding> {
+        match lit.value().as_str() {
+            "writable" => Ok(Encoding::Writable),
+            "vint" => Ok(Encoding::VInt),
+            "text" => Ok(Encoding::Text),
+            other => Err(Error::new_spanned(
+                lit,
+                format!(
+                    "unknown encoding `{}`, expected writable, vint or text",
+                    other
+                ),
+            )),
+        }
+    }
+
+    fn read(self) -> TokenStream2 {
+        match self {
+            Encoding::Writable => quote!(::sequencefile::writable::Writable::read(buf)),
+            Encoding::VInt => quote!(::sequencefile::writable::VarInt::read_vint(buf)),
+            Encoding::Text => quote!(::sequencefile::read_text(buf)),
+        }
+    }
+
+    fn write(self, value: TokenStream2) -> TokenStream2 {
+        match self {
+            Encoding::Writable => quote!(::sequencefile::writable::Writable::write(#value, buf)?),
+            Encoding::VInt => quote!(::sequencefile::writable::VarInt::write_vint(#value, buf)?),
+            Encoding::Text => quote!(::sequencefile::write_text(buf, #value)?),
+        }
+    }
+}
+
+enum Layout {
+    Skip,
+    Single(Encoding),
+    Seq(Encoding),
+    Map(Encoding, Encoding),
+}
+
+fn field_layout(field: &Field) -> Result<Layout> {
+    let mut skip = false;
+    let mut seq = false;
+    let mut map = false;
+    let mut encoding = None;
+    let mut key = None;
+    let mut value = None;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("writable")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+            } else if meta.path.is_ident("seq") {
+                seq = true;
+            } else if meta.path.is_ident("map") {
+                map = true;
+            } else if meta.path.is_ident("vint") {
+                encoding = Some(Encoding::VInt);
+            } else if meta.path.is_ident("text") {
+                encoding = Some(Encoding::Text);
+            } else if meta.path.is_ident("key") {
+                key = Some(Encoding::parse(&meta.value()?.parse()?)?);
+            } else if meta.path.is_ident("value") {
+                value = Some(Encoding::parse(&meta.value()?.parse()?)?);
+            } else {
+                return Err(meta.error("unsupported writable attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    if (key.is_some() || value.is_some()) && !map {
+        return Err(Error::new_spanned(
+            field,
+            "`key` and `value` are only valid on `map` fields",
+        ));
+    }
+
+    let encoding = encoding.unwrap_or(Encoding::Writable);
+    match (skip, seq, map) {
+        (true, false, false) if encoding == Encoding::Writable => Ok(Layout::Skip),
+        (false, false, false) => Ok(Layout::Single(encoding)),
+        (false, true, false) => Ok(Layout::Seq(encoding)),
+        (false, false, true) => Ok(Layout::Map(
+            key.unwrap_or(encoding),
+            value.unwrap_or(encoding),
+        )),
+        _ => Err(Error::new_spanned(
+            field,
+            "conflicting writable attributes, `skip`, `seq` and `map` are mutually exclusive",
+        )),
+    }
+}
+
+fn read_field(layout: &Layout) -> TokenStream2 {
+    match *layout {
+        Layout::Skip => quote!(::core::default::Default::default()),
+        Layout::Single(encoding) => {
+            let read = encoding.read();
+            quote!(#read?)
+        }
+        Layout::Seq(encoding) => {
+            let elem = encoding.read();
+            quote!(::sequencefile::writable::read_seq(buf, |buf| #elem)?)
+        }
+        Layout::Map(key, value) => {
+            let key = key.read();
+            let value = value.read();
+            quote!(::sequencefile::writable::read_map(
+                buf,
+                |buf| #key,
+                |buf| #value
+            )?)
+        }
+    }
+}
+
+fn write_field(layout: &Layout, access: TokenStream2) -> TokenStream2 {
+    match *layout {
+        Layout::Skip => quote!(),
+        Layout::Single(encoding) => {
+            let write = encoding.write(quote!(&#access));
+            quote!(#write;)
+        }
+        Layout::Seq(encoding) => {
+            let elem = encoding.write(quote!(elem));
+            quote!(::sequencefile::writable::write_seq(buf, &#access, |buf, elem| {
+                #elem;
+                Ok(())
+            })?;)
+        }
+        Layout::Map(key, value) => {
+            let key = key.write(quote!(key));
+            let value = value.write(quote!(value));
+            quote!(::sequencefile::writable::write_map(
+                buf,
+                &#access,
+                |buf, key| {
+                    #key;
+                    Ok(())
+                },
+                |buf, value| {
+                    #value;
+                    Ok(())
+                }
+            )?;)
+        }
+    }
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => data.fields.clone(),
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "Writable can only be derived for structs",
+            ))
+        }
+    };
+
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(ty) = param {
+            ty.bounds
+                .push(parse_quote!(::sequencefile::writable::Writable));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let layout = field_layout(field)?;
+        let member = match &field.ident {
+            Some(ident) => ident.to_token_stream(),
+            None => syn::Index::from(i).to_token_stream(),
+        };
+        let read = read_field(&layout);
+        reads.push(quote!(#member: #read));
+        writes.push(write_field(&layout, quote!(self.#member)));
+    }
+
+    let construct = match fields {
+        Fields::Unit => quote!(#name),
+        _ => quote!(#name { #(#reads),* }),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::sequencefile::writable::Writable for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn read(buf: &mut impl ::std::io::Read) -> ::sequencefile::Result<Self>
+            where
+                Self: Sized,
+            {
+                Ok(#construct)
+            }
+
+            #[allow(unused_variables)]
+            fn write(&self, buf: &mut impl ::std::io::Write) -> ::sequencefile::Result<()> {
+                #(#writes)*
+                Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,115 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Cursor;
+
+use sequencefile::writable::Writable;
+use sequencefile::{Reader, Text};
+
+#[derive(Debug, PartialEq, Writable)]
+struct Complex {
+    s1: i64,
+    s2: u8,
+    s3: i16,
+    #[writable(map, text)]
+    s4: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Cache(u32);
+
+#[derive(Debug, PartialEq, Writable)]
+struct Attributed {
+    #[writable(vint)]
+    small: i32,
+    #[writable(vint)]
+    large: i64,
+    #[writable(text)]
+    name: String,
+    #[writable(seq)]
+    ids: Vec<u16>,
+    #[writable(seq, vint)]
+    deltas: Vec<i64>,
+    #[writable(map, key = "text", value = "vint")]
+    counts: BTreeMap<String, i32>,
+    #[writable(skip)]
+    cache: Cache,
+}
+
+#[derive(Debug, PartialEq, Writable)]
+struct Pair<T>(T, #[writable(text)] String);
+
+#[derive(Debug, PartialEq, Writable)]
+struct Empty;
+
+fn round_trip<T: Writable>(value: &T) -> T {
+    let mut buf = vec![];
+    value.write(&mut buf).unwrap();
+    T::read(&mut Cursor::new(buf)).unwrap()
+}
+
+#[test]
+fn reads_complex_file() {
+    let file = File::open("../test_data/complex.seq").unwrap();
+    let reader = Reader::<File, Text, Complex>::new(file).unwrap();
+
+    let values: Vec<_> = reader.map(|kv| kv.unwrap().1).collect();
+
+    assert_eq!(1000, values.len());
+    assert!(values.iter().any(|v| !v.s4.is_empty()));
+}
+
+#[test]
+fn writes_fields_in_order() {
+    let mut s4 = HashMap::new();
+    s4.insert("k".to_string(), "v".to_string());
+    let value = Complex {
+        s1: 1,
+        s2: 2,
+        s3: 3,
+        s4,
+    };
+
+    let mut buf = vec![];
+    value.write(&mut buf).unwrap();
+
+    assert_eq!(
+        vec![0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 3, 0, 0, 0, 1, 1, b'k', 1, b'v'],
+        buf
+    );
+    assert_eq!(value, round_trip(&value));
+}
+
+#[test]
+fn round_trips_attributes() {
+    let mut counts = BTreeMap::new();
+    counts.insert("a".to_string(), 1);
+    counts.insert("b".to_string(), -300);
+    let value = Attributed {
+        small: 1000,
+        large: -1 << 40,
+        name: "héllo".to_string(),
+        ids: vec![1, 2, 3],
+        deltas: vec![0, -1, 1 << 20],
+        counts,
+        cache: Cache(7),
+    };
+
+    let read = round_trip(&value);
+
+    assert_eq!(Cache::default(), read.cache);
+    assert_eq!(
+        Attributed {
+            cache: Cache(7),
+            ..read
+        },
+        value
+    );
+}
+
+#[test]
+fn round_trips_tuple_and_unit_structs() {
+    let pair = Pair(42i32, "answer".to_string());
+
+    assert_eq!(pair, round_trip(&pair));
+    assert_eq!(Empty, round_trip(&Empty));
+}
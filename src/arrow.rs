@@ -0,0 +1,549 @@
+//! Reading sequencefiles into Arrow `RecordBatch`es. Known Writables map to a column each
+//! (Text to Utf8, LongWritable to Int64, BytesWritable to Binary, ...), other classes to
+//! Binary columns of their serialized bytes, unless a `ColumnMapper` is given for them.
+//!
+//! # Example
+//! ```no_run
+//! # use arrow_schema::{DataType, Field};
+//! # use sequencefile::arrow::{BatchReader, StructColumns};
+//! # use sequencefile::value::Value;
+//! # use sequencefile::writable::Writable;
+//! # use sequencefile::Reader;
+//! # use std::fs::File;
+//! # struct Person {
+//! #     name: String,
+//! #     age: i32,
+//! # }
+//! # impl Writable for Person {
+//! #     fn read(buf: &mut impl std::io::Read) -> sequencefile::Result<Self> {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! # fn main() -> sequencefile::Result<()> {
+//! # let path = "people.seq";
+//! let reader = Reader::new(File::open(path)?)?;
+//! for batch in BatchReader::new(reader).batch_size(4096) {
+//!     let batch = batch?;
+//!     println!("{} rows", batch.num_rows());
+//! }
+//!
+//! // a custom value struct spread over several columns
+//! let columns = StructColumns::new(
+//!     vec![Field::new("name", DataType::Utf8, true), Field::new("age", DataType::Int32, true)],
+//!     |person: Person| vec![Value::Text(person.name), Value::Int(person.age)],
+//! )?;
+//! let reader = Reader::new(File::open(path)?)?;
+//! let batches = BatchReader::new(reader).value_columns(columns);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::reader::Reader;
+use crate::value::{self, Value};
+use crate::writable::Writable;
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Float32Builder, Float64Builder, Int16Builder, Int32Builder,
+    Int64Builder, Int8Builder, StringBuilder,
+};
+use arrow_array::{ArrayRef, NullArray, RecordBatch, RecordBatchOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::fmt;
+use std::io::{self, Cursor};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Default number of records per batch
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// Arrow type of a Writable class, Binary for classes without a mapping
+pub fn data_type(class: &str) -> DataType {
+    match class {
+        value::NULL_WRITABLE => DataType::Null,
+        value::BOOLEAN_WRITABLE => DataType::Boolean,
+        value::BYTE_WRITABLE => DataType::Int8,
+        value::SHORT_WRITABLE => DataType::Int16,
+        value::INT_WRITABLE | value::VINT_WRITABLE => DataType::Int32,
+        value::LONG_WRITABLE | value::VLONG_WRITABLE => DataType::Int64,
+        value::FLOAT_WRITABLE => DataType::Float32,
+        value::DOUBLE_WRITABLE => DataType::Float64,
+        value::TEXT => DataType::Utf8,
+        _ => DataType::Binary,
+    }
+}
+
+/// Turns serialized keys or values into one or more Arrow columns
+pub trait ColumnMapper {
+    /// Fields of the columns produced
+    fn fields(&self) -> Vec<Field>;
+
+    /// Appends a serialized key or uncompressed value
+    fn append(&mut self, bytes: &[u8]) -> Result<()>;
+
+    /// Returns the columns of everything appended since the last call
+    fn finish(&mut self) -> Vec<ArrayRef>;
+}
+
+/// Single column of a Writable class, decoded with `Value::decode`
+#[derive(Debug)]
+pub struct WritableColumn {
+    name: String,
+    class: String,
+    builder: ColumnBuilder,
+}
+
+impl WritableColumn {
+    /// Column `name` holding Writables of the Java class
+    pub fn new(name: &str, class: &str) -> WritableColumn {
+        WritableColumn {
+            name: name.to_string(),
+            class: class.to_string(),
+            builder: ColumnBuilder::new(&data_type(class))
+                .expect("types of Writables are supported"),
+        }
+    }
+}
+
+impl ColumnMapper for WritableColumn {
+    fn fields(&self) -> Vec<Field> {
+        vec![Field::new(&self.name, data_type(&self.class), true)]
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result<()> {
+        match Value::decode(&self.class, bytes)? {
+            Value::Raw(bytes) => self.builder.append(Value::Bytes(bytes)),
+            value => self.builder.append(value),
+        }
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        vec![self.builder.finish()]
+    }
+}
+
+/// No columns at all, e.g. to only read keys
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Skip;
+
+impl ColumnMapper for Skip {
+    fn fields(&self) -> Vec<Field> {
+        Vec::new()
+    }
+
+    fn append(&mut self, _bytes: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        Vec::new()
+    }
+}
+
+/// Columns of a custom Writable, each value is read as `T` and mapped to one `Value` per field.
+/// `Value::Null` appends a null.
+pub struct StructColumns<T, F> {
+    fields: Vec<Field>,
+    builders: Vec<ColumnBuilder>,
+    map: F,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T: Writable, F: FnMut(T) -> Vec<Value>> StructColumns<T, F> {
+    /// Columns for the given fields, filled with the values `map` returns for each record
+    ///
+    /// # Failures
+    /// Returns `Error::Serialization` if a field's type isn't one a `Value` maps to, i.e.
+    /// not Null, Boolean, Int8-64, Float32/64, Utf8 or Binary
+    pub fn new(fields: Vec<Field>, map: F) -> Result<StructColumns<T, F>> {
+        let builders = fields
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type()))
+            .collect::<Result<_>>()?;
+        Ok(StructColumns {
+            fields,
+            builders,
+            map,
+            _t: PhantomData,
+        })
+    }
+}
+
+impl<T: Writable, F: FnMut(T) -> Vec<Value>> ColumnMapper for StructColumns<T, F> {
+    fn fields(&self) -> Vec<Field> {
+        self.fields.clone()
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result<()> {
+        let values = (self.map)(T::read(&mut Cursor::new(bytes))?);
+        if values.len() != self.builders.len() {
+            return Err(Error::Serialization(format!(
+                "expected {} column values, got {}",
+                self.builders.len(),
+                values.len()
+            )));
+        }
+        for (builder, value) in self.builders.iter_mut().zip(values) {
+            builder.append(value)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        self.builders.iter_mut().map(|b| b.finish()).collect()
+    }
+}
+
+impl<T, F> fmt::Debug for StructColumns<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StructColumns")
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+/// Iterates a sequencefile as `RecordBatch`es of up to `batch_size` records. Keys go to a
+/// `key` column and values to a `value` column by default. Iteration stops after the first
+/// error, as the columns may then hold a key without its value.
+pub struct BatchReader<R: io::Read> {
+    reader: Reader<R, Vec<u8>, Vec<u8>>,
+    keys: Box<dyn ColumnMapper + Send>,
+    values: Box<dyn ColumnMapper + Send>,
+    batch_size: usize,
+    is_error: bool,
+}
+
+impl<R: io::Read> BatchReader<R> {
+    /// Batches of a reader's records, typed after the header's key and value classes
+    pub fn new(reader: Reader<R, Vec<u8>, Vec<u8>>) -> BatchReader<R> {
+        let keys = WritableColumn::new("key", &reader.header.key_class);
+        let values = WritableColumn::new("value", &reader.header.value_class);
+        BatchReader {
+            reader,
+            keys: Box::new(keys),
+            values: Box::new(values),
+            batch_size: DEFAULT_BATCH_SIZE,
+            is_error: false,
+        }
+    }
+
+    /// Sets the maximum number of records per batch
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Replaces the columns of keys
+    pub fn key_columns<M: ColumnMapper + Send + 'static>(mut self, mapper: M) -> Self {
+        self.keys = Box::new(mapper);
+        self
+    }
+
+    /// Replaces the columns of values
+    pub fn value_columns<M: ColumnMapper + Send + 'static>(mut self, mapper: M) -> Self {
+        self.values = Box::new(mapper);
+        self
+    }
+
+    /// Schema of the batches, key columns first
+    pub fn schema(&self) -> SchemaRef {
+        let mut fields = self.keys.fields();
+        fields.extend(self.values.fields());
+        Arc::new(Schema::new(fields))
+    }
+
+    /// Header of the file
+    pub fn header(&self) -> &crate::Header {
+        &self.reader.header
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let mut rows = 0;
+        while rows < self.batch_size {
+            match self.reader.next() {
+                Some(kv) => {
+                    let (key, value) = kv?;
+                    self.keys.append(&key)?;
+                    self.values.append(&value)?;
+                    rows += 1;
+                }
+                None => break,
+            }
+        }
+        if rows == 0 {
+            return Ok(None);
+        }
+
+        let mut columns = self.keys.finish();
+        columns.extend(self.values.finish());
+        let options = RecordBatchOptions::new().with_row_count(Some(rows));
+        RecordBatch::try_new_with_options(self.schema(), columns, &options)
+            .map(Some)
+            .map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+impl<R: io::Read> Iterator for BatchReader<R> {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch>> {
+        if self.is_error {
+            return None;
+        }
+
+        match self.next_batch() {
+            Ok(batch) => batch.map(Ok),
+            Err(e) => {
+                self.is_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: io::Read> fmt::Debug for BatchReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BatchReader")
+            .field("schema", &self.schema())
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+// builds a column of any type a Value can be appended to
+enum ColumnBuilder {
+    Null(usize),
+    Boolean(BooleanBuilder),
+    Int8(Int8Builder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> Result<ColumnBuilder> {
+        Ok(match data_type {
+            DataType::Null => ColumnBuilder::Null(0),
+            DataType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::new()),
+            DataType::Int8 => ColumnBuilder::Int8(Int8Builder::new()),
+            DataType::Int16 => ColumnBuilder::Int16(Int16Builder::new()),
+            DataType::Int32 => ColumnBuilder::Int32(Int32Builder::new()),
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::new()),
+            DataType::Float32 => ColumnBuilder::Float32(Float32Builder::new()),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::new()),
+            DataType::Utf8 => ColumnBuilder::Utf8(StringBuilder::new()),
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            other => {
+                return Err(Error::Serialization(format!(
+                    "unsupported column type {:?}",
+                    other
+                )))
+            }
+        })
+    }
+
+    fn append(&mut self, value: Value) -> Result<()> {
+        match (self, value) {
+            (ColumnBuilder::Null(len), _) => *len += 1,
+            (ColumnBuilder::Boolean(b), Value::Boolean(v)) => b.append_value(v),
+            (ColumnBuilder::Int8(b), Value::Byte(v)) => b.append_value(v),
+            (ColumnBuilder::Int16(b), Value::Short(v)) => b.append_value(v),
+            (ColumnBuilder::Int32(b), Value::Int(v)) => b.append_value(v),
+            (ColumnBuilder::Int64(b), Value::Long(v)) => b.append_value(v),
+            (ColumnBuilder::Float32(b), Value::Float(v)) => b.append_value(v),
+            (ColumnBuilder::Float64(b), Value::Double(v)) => b.append_value(v),
+            (ColumnBuilder::Utf8(b), Value::Text(v)) => b.append_value(v),
+            (ColumnBuilder::Binary(b), Value::Bytes(v))
+            | (ColumnBuilder::Binary(b), Value::Raw(v)) => b.append_value(v),
+            (builder, Value::Null) => builder.append_null(),
+            (builder, value) => {
+                return Err(Error::Serialization(format!(
+                    "cannot append {:?} to a {:?} column",
+                    value,
+                    builder.data_type()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            ColumnBuilder::Null(len) => *len += 1,
+            ColumnBuilder::Boolean(b) => b.append_null(),
+            ColumnBuilder::Int8(b) => b.append_null(),
+            ColumnBuilder::Int16(b) => b.append_null(),
+            ColumnBuilder::Int32(b) => b.append_null(),
+            ColumnBuilder::Int64(b) => b.append_null(),
+            ColumnBuilder::Float32(b) => b.append_null(),
+            ColumnBuilder::Float64(b) => b.append_null(),
+            ColumnBuilder::Utf8(b) => b.append_null(),
+            ColumnBuilder::Binary(b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Null(len) => Arc::new(NullArray::new(std::mem::take(len))),
+            ColumnBuilder::Boolean(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int16(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(b) => Arc::new(b.finish()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnBuilder::Null(_) => DataType::Null,
+            ColumnBuilder::Boolean(_) => DataType::Boolean,
+            ColumnBuilder::Int8(_) => DataType::Int8,
+            ColumnBuilder::Int16(_) => DataType::Int16,
+            ColumnBuilder::Int32(_) => DataType::Int32,
+            ColumnBuilder::Int64(_) => DataType::Int64,
+            ColumnBuilder::Float32(_) => DataType::Float32,
+            ColumnBuilder::Float64(_) => DataType::Float64,
+            ColumnBuilder::Utf8(_) => DataType::Utf8,
+            ColumnBuilder::Binary(_) => DataType::Binary,
+        }
+    }
+}
+
+impl fmt::Debug for ColumnBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ColumnBuilder({:?})", self.data_type())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchReader, StructColumns};
+    use crate::reader::Reader;
+    use crate::tests::test_config;
+    use crate::value::Value;
+    use crate::writable::{BytesWritable, Writable};
+    use crate::writer::{Writer, WriterConfig};
+    use crate::{Codec, CompressionType, Text};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Int32Type, Int64Type};
+    use arrow_schema::{DataType, Field};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_known_writables_in_batches() {
+        let reader =
+            Reader::new(File::open("test_data/abc_long_text_snappy_block.seq").unwrap()).unwrap();
+        let batches: Vec<_> = BatchReader::new(reader)
+            .batch_size(10)
+            .map(|b| b.unwrap())
+            .collect();
+
+        assert_eq!(
+            vec![10, 10, 6],
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>()
+        );
+        let schema = batches[0].schema();
+        assert_eq!(&DataType::Int64, schema.field(0).data_type());
+        assert_eq!(&DataType::Utf8, schema.field(1).data_type());
+        assert_eq!("value", schema.field(1).name());
+
+        let keys = batches[2].column(0).as_primitive::<Int64Type>();
+        let values = batches[2].column(1).as_string::<i32>();
+        assert_eq!(25, keys.value(5));
+        assert_eq!("\0Z", values.value(5));
+    }
+
+    #[test]
+    fn reads_bytes_and_custom_columns() {
+        #[derive(Debug)]
+        struct Point(i32, i32);
+
+        impl Writable for Point {
+            fn read(buf: &mut impl std::io::Read) -> crate::Result<Self> {
+                Ok(Point(i32::read(buf)?, i32::read(buf)?))
+            }
+
+            fn write(&self, buf: &mut impl std::io::Write) -> crate::Result<()> {
+                self.0.write(buf)?;
+                self.1.write(buf)
+            }
+        }
+
+        let config = WriterConfig::new("org.apache.hadoop.io.BytesWritable", "com.example.Point")
+            .compression(CompressionType::Record, Codec::Gzip);
+        let mut writer = Writer::<_, BytesWritable, Point>::new(Vec::new(), config).unwrap();
+        for i in 0..5 {
+            writer
+                .append(
+                    &BytesWritable(vec![i as u8; i]),
+                    &Point(i as i32, -(i as i32)),
+                )
+                .unwrap();
+        }
+        let file = writer.close().unwrap();
+
+        // unknown classes are kept as their serialized bytes
+        let reader = Reader::new(Cursor::new(&file)).unwrap();
+        let batch = BatchReader::new(reader).next().unwrap().unwrap();
+        assert_eq!(&DataType::Binary, batch.schema().field(1).data_type());
+        assert_eq!(&[3, 3, 3], batch.column(0).as_binary::<i32>().value(3));
+        assert_eq!(8, batch.column(1).as_binary::<i32>().value(3).len());
+
+        let columns = StructColumns::new(
+            vec![
+                Field::new("x", DataType::Int32, true),
+                Field::new("y", DataType::Int32, true),
+                Field::new("label", DataType::Utf8, true),
+            ],
+            |p: Point| {
+                let label = if p.0 % 2 == 0 {
+                    Value::Text(format!("even {}", p.0))
+                } else {
+                    Value::Null
+                };
+                vec![Value::Int(p.0), Value::Int(p.1), label]
+            },
+        )
+        .unwrap();
+        let reader = Reader::new(Cursor::new(&file)).unwrap();
+        let batches = BatchReader::new(reader).value_columns(columns);
+        assert_eq!(4, batches.schema().fields().len());
+
+        let batch = batches.map(|b| b.unwrap()).next().unwrap();
+        assert_eq!(-4, batch.column(2).as_primitive::<Int32Type>().value(4));
+        assert_eq!("even 2", batch.column(3).as_string::<i32>().value(2));
+        assert!(batch.column(3).is_null(1));
+    }
+
+    #[test]
+    fn reports_mismatched_custom_columns() {
+        let config = test_config();
+        let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+        writer.append(&1, &Text::from("one")).unwrap();
+        let file = writer.close().unwrap();
+
+        let columns =
+            StructColumns::new(vec![Field::new("n", DataType::Int64, true)], |t: Text| {
+                vec![Value::Text(t.to_string().into_owned())]
+            })
+            .unwrap();
+        let reader = Reader::new(Cursor::new(file)).unwrap();
+        let mut batches = BatchReader::new(reader).value_columns(columns);
+        assert!(batches.next().unwrap().is_err());
+        assert!(batches.next().is_none());
+
+        let unsupported = StructColumns::new(
+            vec![Field::new("day", DataType::Date32, true)],
+            |t: Text| vec![Value::Text(t.to_string().into_owned())],
+        );
+        assert!(unsupported.is_err());
+    }
+}
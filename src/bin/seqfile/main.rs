@@ -0,0 +1,475 @@
+//! `seqfile`, a command-line tool for inspecting Hadoop sequencefiles
+
+extern crate clap;
+extern crate sequencefile;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use sequencefile::convert::Converter;
+use sequencefile::import::Importer;
+use sequencefile::json::{BytesEncoding, JsonLinesWriter};
+#[cfg(feature = "parquet")]
+use sequencefile::parquet::{Compression, ParquetConverter};
+use sequencefile::value::Value;
+use sequencefile::verify::Verifier;
+use sequencefile::{Codec, CompressionType, Reader, Result};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+type RawReader = Reader<File, Vec<u8>, Vec<u8>>;
+
+/// Inspect Hadoop sequencefiles
+#[derive(Debug, Parser)]
+#[command(name = "seqfile", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print version, key/value classes, compression, metadata and sync marker
+    Header {
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Print every record as tab separated key and value, like `hadoop fs -text`
+    #[command(alias = "text")]
+    Cat {
+        #[command(flatten)]
+        format: FormatArgs,
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Print the first records
+    Head {
+        /// Number of records to print
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: usize,
+        #[command(flatten)]
+        format: FormatArgs,
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Print the number of records
+    Count {
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Print record and block counts, compressed and raw sizes
+    Stat {
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Check the whole file, reporting every problem with its byte offset
+    Verify {
+        /// Also decode keys and values of known Writable classes
+        #[arg(long)]
+        decode: bool,
+        /// Stop after this many problems
+        #[arg(long, default_value_t = 100)]
+        max_problems: usize,
+        /// Sequencefile to read
+        file: PathBuf,
+    },
+    /// Rewrite a file with another compression type or codec, keeping classes and metadata
+    Convert {
+        /// Output compression type, defaults to the input's
+        #[arg(long, value_enum)]
+        compression: Option<CompressionArg>,
+        /// Output codec for record and block compression, only with `--compression`
+        #[arg(long, value_enum, requires = "compression")]
+        codec: Option<CodecArg>,
+        /// Metadata entry to add or override, as KEY=VALUE
+        #[arg(long = "metadata", value_name = "KEY=VALUE", value_parser = parse_metadata)]
+        metadata: Vec<(String, String)>,
+        /// Sequencefile to read
+        input: PathBuf,
+        /// Sequencefile to write
+        output: PathBuf,
+    },
+    /// Write CSV or JSON Lines rows as records
+    Import {
+        /// Input format
+        #[arg(long, value_enum)]
+        format: ImportFormatArg,
+        /// Key column and its Writable class, e.g. id:LongWritable
+        #[arg(long, value_name = "COLUMN:CLASS", value_parser = parse_column)]
+        key: (String, String),
+        /// Value column and its Writable class, e.g. name:Text
+        #[arg(long, value_name = "COLUMN:CLASS", value_parser = parse_column)]
+        value: (String, String),
+        /// CSV field delimiter
+        #[arg(long, default_value_t = ',')]
+        delimiter: char,
+        /// Encoding of BytesWritable and custom class cells
+        #[arg(long, value_enum, default_value_t = BytesArg::Base64)]
+        bytes: BytesArg,
+        /// Output compression type
+        #[arg(long, value_enum, default_value_t = CompressionArg::None)]
+        compression: CompressionArg,
+        /// Output codec for record and block compression
+        #[arg(long, value_enum, default_value_t = CodecArg::Default)]
+        codec: CodecArg,
+        /// CSV or JSON Lines file to read
+        input: PathBuf,
+        /// Sequencefile to write
+        output: PathBuf,
+    },
+    /// Convert a file, or a directory of part files, to Parquet
+    #[cfg(feature = "parquet")]
+    Parquet {
+        /// Parquet compression, e.g. snappy, gzip(6), zstd(3) or uncompressed
+        #[arg(long, default_value = "snappy", value_parser = parse_parquet_compression)]
+        compression: Compression,
+        /// Maximum number of records per row group
+        #[arg(long, default_value_t = 1024 * 1024)]
+        row_group_size: usize,
+        /// Sequencefile or directory of part files to read
+        input: PathBuf,
+        /// Parquet file to write
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, Args)]
+struct FormatArgs {
+    /// Output format of records
+    #[arg(long, value_enum, default_value_t = FormatArg::Text)]
+    format: FormatArg,
+    /// Encoding of opaque bytes in JSON output
+    #[arg(long, value_enum, default_value_t = BytesArg::Base64)]
+    bytes: BytesArg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum FormatArg {
+    /// tab separated key and value
+    Text,
+    /// one `{"key": ..., "value": ...}` object per line
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ImportFormatArg {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BytesArg {
+    Base64,
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CompressionArg {
+    None,
+    Record,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum CodecArg {
+    /// zlib deflate, Hadoop's DefaultCodec
+    Default,
+    Gzip,
+    Bzip2,
+    Snappy,
+}
+
+fn parse_metadata(entry: &str) -> std::result::Result<(String, String), String> {
+    match entry.find('=') {
+        Some(i) => Ok((entry[..i].to_string(), entry[i + 1..].to_string())),
+        None => Err(format!("expected KEY=VALUE, found '{}'", entry)),
+    }
+}
+
+fn parse_column(column: &str) -> std::result::Result<(String, String), String> {
+    match column.rfind(':') {
+        Some(i) => Ok((column[..i].to_string(), column[i + 1..].to_string())),
+        None => Err(format!("expected COLUMN:CLASS, found '{}'", column)),
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parse_parquet_compression(name: &str) -> std::result::Result<Compression, String> {
+    name.parse().map_err(|e| format!("{}", e))
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    let result = run(cli.command, &mut out).and_then(|ok| Ok(out.flush().map(|_| ok)?));
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        // e.g. piped into `head`
+        Err(sequencefile::Error::IO(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => (),
+        Err(e) => {
+            eprintln!("seqfile: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// false when the command found problems and should exit unsuccessfully
+fn run(command: Command, out: &mut impl Write) -> Result<bool> {
+    match command {
+        Command::Header { file } => header(&file, out)?,
+        Command::Cat { format, file } => records(&file, usize::MAX, &format, out)?,
+        Command::Head {
+            lines,
+            format,
+            file,
+        } => records(&file, lines, &format, out)?,
+        Command::Count { file } => {
+            let count = open(&file)?.try_fold(0u64, |n, kv| kv.map(|_| n + 1))?;
+            writeln!(out, "{}", count)?;
+        }
+        Command::Stat { file } => stat(&file, out)?,
+        Command::Verify {
+            decode,
+            max_problems,
+            file,
+        } => return verify(&file, decode, max_problems, out),
+        Command::Convert {
+            compression,
+            codec,
+            metadata,
+            input,
+            output,
+        } => convert(&input, &output, compression, codec, &metadata)?,
+        Command::Import {
+            format,
+            key,
+            value,
+            delimiter,
+            bytes,
+            compression,
+            codec,
+            input,
+            output,
+        } => {
+            let (compression_type, codec) = compression_settings(compression, codec);
+            let importer = Importer::new(&key.0, &key.1, &value.0, &value.1)
+                .compression(compression_type, codec)
+                .bytes(bytes_encoding(bytes))
+                .delimiter(delimiter);
+            let input = BufReader::new(File::open(input)?);
+            let output = File::create(output)?;
+            let output = match format {
+                ImportFormatArg::Csv => importer.import_csv(input, output)?,
+                ImportFormatArg::Json => importer.import_json(input, output)?,
+            };
+            output.sync_all()?;
+        }
+        #[cfg(feature = "parquet")]
+        Command::Parquet {
+            compression,
+            row_group_size,
+            input,
+            output,
+        } => {
+            let converter = ParquetConverter::new()
+                .compression(compression)
+                .row_group_size(row_group_size);
+            let output = File::create(output)?;
+            let output = if input.is_dir() {
+                converter.convert_dir(&input, output)?
+            } else {
+                converter.convert(File::open(&input)?, output)?
+            };
+            output.sync_all()?;
+        }
+    }
+    Ok(true)
+}
+
+fn open(path: &Path) -> Result<RawReader> {
+    Reader::new(File::open(path)?)
+}
+
+fn header(path: &Path, out: &mut impl Write) -> Result<()> {
+    let header = open(path)?.header;
+
+    writeln!(out, "version: {}", header.version)?;
+    writeln!(out, "key class: {}", header.key_class)?;
+    writeln!(out, "value class: {}", header.value_class)?;
+    writeln!(
+        out,
+        "compression: {}",
+        compression_name(header.compression_type)
+    )?;
+    if let Some(codec) = header.compression_codec {
+        writeln!(out, "codec: {}", codec.class_name())?;
+    }
+
+    let mut metadata: Vec<_> = header.metadata.iter().collect();
+    metadata.sort();
+    writeln!(out, "metadata: {}", metadata.len())?;
+    for (key, value) in metadata {
+        writeln!(out, "  {}: {}", key, value)?;
+    }
+
+    let marker: String = header
+        .sync_marker()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    writeln!(out, "sync marker: {}", marker)?;
+    Ok(())
+}
+
+fn records(path: &Path, limit: usize, format: &FormatArgs, out: &mut impl Write) -> Result<()> {
+    let reader = open(path)?;
+    if format.format == FormatArg::Json {
+        let mut writer = JsonLinesWriter::new(out, &reader.header, bytes_encoding(format.bytes));
+        for kv in reader.take(limit) {
+            let (key, value) = kv?;
+            writer.write(&key, &value)?;
+        }
+        return Ok(());
+    }
+
+    let key_class = reader.header.key_class.clone();
+    let value_class = reader.header.value_class.clone();
+
+    for kv in reader.take(limit) {
+        let (key, value) = kv?;
+        writeln!(
+            out,
+            "{}\t{}",
+            Value::decode(&key_class, &key)?,
+            Value::decode(&value_class, &value)?
+        )?;
+    }
+    Ok(())
+}
+
+fn stat(path: &Path, out: &mut impl Write) -> Result<()> {
+    let file_size = path.metadata()?.len();
+    let mut reader = open(path)?;
+    let header_size = reader.position();
+    let compression_type = reader.header.compression_type;
+
+    let (mut records, mut blocks, mut key_bytes, mut value_bytes) = (0u64, 0u64, 0u64, 0u64);
+    let mut position = header_size;
+    while let Some(kv) = reader.next() {
+        let (key, value) = kv?;
+        records += 1;
+        key_bytes += key.len() as u64;
+        value_bytes += value.len() as u64;
+        // the reader only moves on when it loads the next block
+        if reader.position() != position {
+            position = reader.position();
+            blocks += 1;
+        }
+    }
+
+    let data_size = file_size - header_size;
+    let raw_size = key_bytes + value_bytes;
+    writeln!(out, "records: {}", records)?;
+    if compression_type == CompressionType::Block {
+        writeln!(out, "blocks: {}", blocks)?;
+    }
+    writeln!(out, "file size: {}", file_size)?;
+    writeln!(out, "header size: {}", header_size)?;
+    writeln!(out, "data size: {}", data_size)?;
+    writeln!(out, "raw key size: {}", key_bytes)?;
+    writeln!(out, "raw value size: {}", value_bytes)?;
+    if data_size > 0 {
+        writeln!(
+            out,
+            "compression ratio: {:.2}",
+            raw_size as f64 / data_size as f64
+        )?;
+    }
+    Ok(())
+}
+
+fn verify(path: &Path, decode: bool, max_problems: usize, out: &mut impl Write) -> Result<bool> {
+    let header = open(path)?.header;
+    let mut verifier = Verifier::new().max_problems(max_problems);
+    if decode {
+        verifier = verifier.check_records(|key, value| {
+            Value::decode(&header.key_class, key)?;
+            Value::decode(&header.value_class, value)?;
+            Ok(())
+        });
+    }
+
+    let report = verifier.verify(File::open(path)?)?;
+    for problem in &report.problems {
+        writeln!(out, "{}", problem)?;
+    }
+    writeln!(out, "records: {}", report.records)?;
+    if report.header.compression_type == CompressionType::Block {
+        writeln!(out, "blocks: {}", report.blocks)?;
+    }
+    writeln!(out, "sync markers: {}", report.sync_markers)?;
+    writeln!(out, "skipped bytes: {}", report.skipped_bytes)?;
+    if report.truncated {
+        writeln!(out, "problems: {} (stopped early)", report.problems.len())?;
+    } else {
+        writeln!(out, "problems: {}", report.problems.len())?;
+    }
+    Ok(report.is_ok())
+}
+
+fn convert(
+    input: &Path,
+    output: &Path,
+    compression: Option<CompressionArg>,
+    codec: Option<CodecArg>,
+    metadata: &[(String, String)],
+) -> Result<()> {
+    let mut converter = Converter::new();
+    if let Some(compression) = compression {
+        let codec = codec.unwrap_or(CodecArg::Default);
+        let (compression_type, codec) = compression_settings(compression, codec);
+        converter = converter.compression(compression_type, codec);
+    }
+    for (key, value) in metadata {
+        converter = converter.metadata(key, value);
+    }
+
+    let input = BufReader::new(File::open(input)?);
+    converter
+        .convert(input, File::create(output)?)?
+        .sync_all()?;
+    Ok(())
+}
+
+fn compression_settings(compression: CompressionArg, codec: CodecArg) -> (CompressionType, Codec) {
+    let compression_type = match compression {
+        CompressionArg::None => CompressionType::None,
+        CompressionArg::Record => CompressionType::Record,
+        CompressionArg::Block => CompressionType::Block,
+    };
+    let codec = match codec {
+        CodecArg::Default => Codec::Default,
+        CodecArg::Gzip => Codec::Gzip,
+        CodecArg::Bzip2 => Codec::Bzip2,
+        CodecArg::Snappy => Codec::Snappy,
+    };
+    (compression_type, codec)
+}
+
+fn bytes_encoding(bytes: BytesArg) -> BytesEncoding {
+    match bytes {
+        BytesArg::Base64 => BytesEncoding::Base64,
+        BytesArg::Hex => BytesEncoding::Hex,
+    }
+}
+
+fn compression_name(compression_type: CompressionType) -> &'static str {
+    match compression_type {
+        CompressionType::None => "none",
+        CompressionType::Record => "record",
+        CompressionType::Block => "block",
+    }
+}
@@ -0,0 +1,366 @@
+//! Hadoop's `DynamicBloomFilter`, as serialized next to a `BloomMapFile`, and the Jenkins
+//! and Murmur hashes from `org.apache.hadoop.util.hash` it is built on
+
+use crate::errors::{Error, Result};
+use crate::writable::Writable;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io;
+
+const VERSION: i32 = -1;
+
+/// Hash function of a filter, `hadoop.util.hash.type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashType {
+    /// org.apache.hadoop.util.hash.JenkinsHash
+    Jenkins,
+    /// org.apache.hadoop.util.hash.MurmurHash, Hadoop's default
+    Murmur,
+}
+
+impl HashType {
+    fn from_id(id: u8) -> Result<HashType> {
+        match id {
+            0 => Ok(HashType::Jenkins),
+            1 => Ok(HashType::Murmur),
+            _ => Err(Error::Serialization(format!("unknown hash type: {}", id))),
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            HashType::Jenkins => 0,
+            HashType::Murmur => 1,
+        }
+    }
+
+    /// Hashes `data` with the given seed, like `Hash.hash(byte[], int)`
+    pub fn hash(self, data: &[u8], seed: i32) -> i32 {
+        match self {
+            HashType::Jenkins => jenkins_hash(data, seed),
+            HashType::Murmur => murmur_hash(data, seed),
+        }
+    }
+}
+
+/// hadoop.util.bloom.DynamicBloomFilter, a list of standard Bloom filters where a new
+/// one is started once the current one holds `nr` keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicBloomFilter {
+    vector_size: i32,
+    nb_hash: i32,
+    hash_type: HashType,
+    nr: i32,
+    current_nb_record: i32,
+    matrix: Vec<BloomFilter>,
+}
+
+impl DynamicBloomFilter {
+    /// Empty filter of `vector_size` bits and `nb_hash` hashes per row, `nr` keys per row
+    pub fn new(vector_size: i32, nb_hash: i32, hash_type: HashType, nr: i32) -> DynamicBloomFilter {
+        DynamicBloomFilter {
+            vector_size,
+            nb_hash,
+            hash_type,
+            nr,
+            current_nb_record: 0,
+            matrix: vec![BloomFilter::new(vector_size)],
+        }
+    }
+
+    /// Adds a key, given as its serialized bytes
+    pub fn add(&mut self, key: &[u8]) {
+        if self.current_nb_record >= self.nr || self.matrix.is_empty() {
+            self.matrix.push(BloomFilter::new(self.vector_size));
+            self.current_nb_record = 0;
+        }
+
+        let positions = self.positions(key);
+        let row = self.matrix.last_mut().expect("at least one row");
+        for position in positions {
+            row.set(position);
+        }
+        self.current_nb_record += 1;
+    }
+
+    /// Tells if a key may have been added, `false` means it definitely was not
+    pub fn membership_test(&self, key: &[u8]) -> bool {
+        let positions = self.positions(key);
+        self.matrix
+            .iter()
+            .any(|row| positions.iter().all(|&p| row.get(p)))
+    }
+
+    // HashFunction.hash, each hash is seeded with the previous one
+    fn positions(&self, key: &[u8]) -> Vec<usize> {
+        let mut seed = 0;
+        (0..self.nb_hash)
+            .map(|_| {
+                seed = self.hash_type.hash(key, seed);
+                (seed % self.vector_size).unsigned_abs() as usize
+            })
+            .collect()
+    }
+}
+
+impl Writable for DynamicBloomFilter {
+    fn read(buf: &mut impl io::Read) -> Result<Self> {
+        let (vector_size, nb_hash, hash_type) = read_filter_header(buf)?;
+        let nr = buf.read_i32::<BigEndian>()?;
+        let current_nb_record = buf.read_i32::<BigEndian>()?;
+        let rows = buf.read_i32::<BigEndian>()?;
+
+        let mut matrix = Vec::with_capacity(rows.max(0) as usize);
+        for _ in 0..rows {
+            let (row_size, _, _) = read_filter_header(buf)?;
+            let mut bits = vec![0; BloomFilter::n_bytes(row_size)];
+            buf.read_exact(&mut bits)?;
+            matrix.push(BloomFilter { bits });
+        }
+
+        Ok(DynamicBloomFilter {
+            vector_size,
+            nb_hash,
+            hash_type,
+            nr,
+            current_nb_record,
+            matrix,
+        })
+    }
+
+    fn write(&self, buf: &mut impl io::Write) -> Result<()> {
+        self.write_filter_header(buf)?;
+        buf.write_i32::<BigEndian>(self.nr)?;
+        buf.write_i32::<BigEndian>(self.current_nb_record)?;
+        buf.write_i32::<BigEndian>(self.matrix.len() as i32)?;
+        for row in &self.matrix {
+            self.write_filter_header(buf)?;
+            buf.write_all(&row.bits)?;
+        }
+        Ok(())
+    }
+}
+
+impl DynamicBloomFilter {
+    fn write_filter_header(&self, buf: &mut impl io::Write) -> Result<()> {
+        buf.write_i32::<BigEndian>(VERSION)?;
+        buf.write_i32::<BigEndian>(self.nb_hash)?;
+        buf.write_u8(self.hash_type.id())?;
+        buf.write_i32::<BigEndian>(self.vector_size)?;
+        Ok(())
+    }
+}
+
+// Filter.readFields, unversioned filters only stored the hash count and used Jenkins
+fn read_filter_header(buf: &mut impl io::Read) -> Result<(i32, i32, HashType)> {
+    let version = buf.read_i32::<BigEndian>()?;
+    let (nb_hash, hash_type) = if version > 0 {
+        (version, HashType::Jenkins)
+    } else if version == VERSION {
+        let nb_hash = buf.read_i32::<BigEndian>()?;
+        (nb_hash, HashType::from_id(buf.read_u8()?)?)
+    } else {
+        return Err(Error::Serialization(format!(
+            "unsupported bloom filter version: {}",
+            version
+        )));
+    };
+
+    let vector_size = buf.read_i32::<BigEndian>()?;
+    if vector_size <= 0 {
+        return Err(Error::Serialization(format!(
+            "bad bloom filter vector size: {}",
+            vector_size
+        )));
+    }
+    Ok((vector_size, nb_hash, hash_type))
+}
+
+// hadoop.util.bloom.BloomFilter bit vector, bit i is stored in byte i / 8 under mask 1 << i % 8
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BloomFilter {
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    fn new(vector_size: i32) -> BloomFilter {
+        BloomFilter {
+            bits: vec![0; BloomFilter::n_bytes(vector_size)],
+        }
+    }
+
+    fn n_bytes(vector_size: i32) -> usize {
+        (vector_size as usize).div_ceil(8)
+    }
+
+    fn set(&mut self, position: usize) {
+        self.bits[position / 8] |= 1 << (position % 8);
+    }
+
+    fn get(&self, position: usize) -> bool {
+        self.bits[position / 8] & (1 << (position % 8)) != 0
+    }
+}
+
+/// org.apache.hadoop.util.hash.MurmurHash
+pub fn murmur_hash(data: &[u8], seed: i32) -> i32 {
+    let m: i32 = 0x5bd1_e995;
+    let r = 24;
+    let mut h = seed ^ data.len() as i32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in chunks.by_ref() {
+        let mut k = i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(m);
+        k ^= ((k as u32) >> r) as i32;
+        k = k.wrapping_mul(m);
+        h = h.wrapping_mul(m);
+        h ^= k;
+    }
+
+    // the tail bytes are sign extended, as Java bytes are
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        if tail.len() >= 3 {
+            h ^= (tail[2] as i8 as i32) << 16;
+        }
+        if tail.len() >= 2 {
+            h ^= (tail[1] as i8 as i32) << 8;
+        }
+        h ^= tail[0] as i8 as i32;
+        h = h.wrapping_mul(m);
+    }
+
+    h ^= ((h as u32) >> 13) as i32;
+    h = h.wrapping_mul(m);
+    h ^= ((h as u32) >> 15) as i32;
+    h
+}
+
+/// org.apache.hadoop.util.hash.JenkinsHash, Bob Jenkins' lookup3 `hashlittle`
+pub fn jenkins_hash(data: &[u8], seed: i32) -> i32 {
+    fn word(bytes: &[u8]) -> u32 {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(0u32, |w, (i, &b)| w.wrapping_add(u32::from(b) << (8 * i)))
+    }
+
+    let init = 0xdead_beef_u32
+        .wrapping_add(data.len() as u32)
+        .wrapping_add(seed as u32);
+    let (mut a, mut b, mut c) = (init, init, init);
+
+    let mut rest = data;
+    while rest.len() > 12 {
+        a = a.wrapping_add(word(&rest[0..4]));
+        b = b.wrapping_add(word(&rest[4..8]));
+        c = c.wrapping_add(word(&rest[8..12]));
+
+        a = a.wrapping_sub(c) ^ c.rotate_left(4);
+        c = c.wrapping_add(b);
+        b = b.wrapping_sub(a) ^ a.rotate_left(6);
+        a = a.wrapping_add(c);
+        c = c.wrapping_sub(b) ^ b.rotate_left(8);
+        b = b.wrapping_add(a);
+        a = a.wrapping_sub(c) ^ c.rotate_left(16);
+        c = c.wrapping_add(b);
+        b = b.wrapping_sub(a) ^ a.rotate_left(19);
+        a = a.wrapping_add(c);
+        c = c.wrapping_sub(b) ^ b.rotate_left(4);
+        b = b.wrapping_add(a);
+
+        rest = &rest[12..];
+    }
+
+    if rest.is_empty() {
+        return c as i32;
+    }
+    a = a.wrapping_add(word(&rest[..rest.len().min(4)]));
+    if rest.len() > 4 {
+        b = b.wrapping_add(word(&rest[4..rest.len().min(8)]));
+    }
+    if rest.len() > 8 {
+        c = c.wrapping_add(word(&rest[8..]));
+    }
+
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(14));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(11));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(25));
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(16));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(4));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(14));
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(24));
+    c as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicBloomFilter, HashType};
+    use crate::writable::Writable;
+
+    const KEYS: [&[u8]; 4] = [
+        b"a",
+        b"hadoop",
+        &[0xff, 0x80, 0x01],
+        b"the quick brown fox jumps",
+    ];
+
+    fn chained(hash_type: HashType, key: &[u8]) -> Vec<i32> {
+        let mut seed = 0;
+        (0..3)
+            .map(|_| {
+                seed = hash_type.hash(key, seed);
+                seed
+            })
+            .collect()
+    }
+
+    // values from Hadoop's MurmurHash and JenkinsHash, each seeded with the previous hash
+    #[test]
+    fn hashes_like_hadoop() {
+        let murmur = [
+            [-1838653602, 1529584742, 507861454],
+            [1118164240, 1275480748, 163227368],
+            [1397589984, 302333493, -972011753],
+            [1206938206, -1029793999, -1480603721],
+        ];
+        let jenkins = [
+            [1490454280, 12273821, 1040820228],
+            [-1215099288, -629084701, -1898109051],
+            [287998601, 59371806, 1889623381],
+            [-522419373, 261258029, -1639476117],
+        ];
+
+        for (i, key) in KEYS.iter().enumerate() {
+            assert_eq!(murmur[i].to_vec(), chained(HashType::Murmur, key));
+            assert_eq!(jenkins[i].to_vec(), chained(HashType::Jenkins, key));
+        }
+    }
+
+    #[test]
+    fn round_trips_and_tests_membership() {
+        let mut filter = DynamicBloomFilter::new(1000, 5, HashType::Murmur, 50);
+        for i in 0..200u32 {
+            filter.add(&i.to_be_bytes());
+        }
+
+        let mut buf = Vec::new();
+        filter.write(&mut buf).unwrap();
+        let read = DynamicBloomFilter::read(&mut &buf[..]).unwrap();
+
+        assert_eq!(filter, read);
+        assert_eq!(4, read.matrix.len());
+        assert!((0..200u32).all(|i| read.membership_test(&i.to_be_bytes())));
+        let false_positives = (200..1200u32)
+            .filter(|i| read.membership_test(&i.to_be_bytes()))
+            .count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+    }
+}
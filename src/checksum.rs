@@ -0,0 +1,447 @@
+//! Hadoop `ChecksumFileSystem` sidecar files: `.name.crc` next to `name`, holding the
+//! magic `crc\0`, an i32 bytes-per-checksum and a big-endian CRC per chunk of data
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::checksum::{ChecksumReader, ChecksumType, ChecksumWriter};
+//! # use sequencefile::{Reader, Text, Writer, WriterConfig};
+//! # fn main() -> sequencefile::Result<()> {
+//! # let config = WriterConfig::new("org.apache.hadoop.io.Text", "org.apache.hadoop.io.LongWritable");
+//! // writes /path/to/seqfile and /path/to/.seqfile.crc
+//! let output = ChecksumWriter::create("/path/to/seqfile")?.checksum_type(ChecksumType::Crc32c);
+//! let mut writer = Writer::<_, Text, i64>::new(output, config)?;
+//! writer.append(&Text::from("key"), &1)?;
+//! writer.close()?.close()?;
+//!
+//! // fails with Error::ChecksumMismatch if the file does not match its .crc
+//! let input = ChecksumReader::open("/path/to/seqfile")?.checksum_type(ChecksumType::Crc32c);
+//! let seqfile = Reader::<_, Text, i64>::new(input)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use byteorder::{BigEndian, ReadBytesExt};
+use crc::{Crc, CRC_32_ISCSI, CRC_32_ISO_HDLC};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes starting every `.crc` file
+pub const CHECKSUM_MAGIC: &[u8] = b"crc\0";
+
+/// Default number of data bytes per checksum, `file.bytes-per-checksum`
+pub const DEFAULT_BYTES_PER_CHECKSUM: usize = 512;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Checksum algorithm, `.crc` files do not record it and Hadoop uses CRC32 for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumType {
+    /// zlib CRC32
+    #[default]
+    Crc32,
+    /// Castagnoli CRC32C
+    Crc32c,
+}
+
+impl ChecksumType {
+    /// Checksum of a chunk
+    pub fn checksum(self, bytes: &[u8]) -> u32 {
+        match self {
+            ChecksumType::Crc32 => CRC32.checksum(bytes),
+            ChecksumType::Crc32c => CRC32C.checksum(bytes),
+        }
+    }
+}
+
+/// Path of the checksum file of `path`, e.g. `dir/.part-00000.crc` for `dir/part-00000`
+pub fn crc_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.crc", name))
+}
+
+/// Verifies data against its `.crc` sidecar while reading it. Each chunk is checked
+/// before any of it is returned, a mismatch fails the read with `Error::ChecksumMismatch`.
+#[derive(Debug)]
+pub struct ChecksumReader<R: Read, C: Read> {
+    data: R,
+    sums: C,
+    checksum_type: ChecksumType,
+    bytes_per_checksum: usize,
+    chunk: Vec<u8>,
+    chunk_position: usize,
+    offset: u64,
+}
+
+impl ChecksumReader<File, BufReader<File>> {
+    /// Opens a file and its `.crc` sidecar
+    ///
+    /// # Failures
+    /// Returns an `Error` if either file cannot be opened or the sidecar header is bad
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ChecksumReader<File, BufReader<File>>> {
+        let path = path.as_ref();
+        let sums = BufReader::new(File::open(crc_path(path))?);
+        ChecksumReader::new(File::open(path)?, sums)
+    }
+}
+
+impl<R: Read, C: Read> ChecksumReader<R, C> {
+    /// Wraps a data stream and the contents of its `.crc` file, reading the header
+    ///
+    /// # Failures
+    /// Returns an `Error` if the `.crc` header is missing or malformed
+    pub fn new(data: R, mut sums: C) -> Result<ChecksumReader<R, C>> {
+        let mut magic = [0; 4];
+        sums.read_exact(&mut magic)?;
+        if magic != CHECKSUM_MAGIC {
+            return Err(Error::BadMagic(
+                String::from_utf8_lossy(&magic).into_owned(),
+            ));
+        }
+
+        let bytes_per_checksum = sums.read_i32::<BigEndian>()?;
+        if bytes_per_checksum <= 0 {
+            return Err(Error::Serialization(format!(
+                "bad bytes per checksum: {}",
+                bytes_per_checksum
+            )));
+        }
+
+        Ok(ChecksumReader {
+            data,
+            sums,
+            checksum_type: ChecksumType::Crc32,
+            bytes_per_checksum: bytes_per_checksum as usize,
+            chunk: Vec::with_capacity(bytes_per_checksum as usize),
+            chunk_position: 0,
+            offset: 0,
+        })
+    }
+
+    /// Sets the checksum algorithm, for sidecars written with CRC32C
+    pub fn checksum_type(mut self, checksum_type: ChecksumType) -> Self {
+        self.checksum_type = checksum_type;
+        self
+    }
+
+    /// Number of data bytes covered by each checksum
+    pub fn bytes_per_checksum(&self) -> usize {
+        self.bytes_per_checksum
+    }
+
+    /// Returns the wrapped data stream
+    pub fn into_inner(self) -> R {
+        self.data
+    }
+
+    // reads and verifies the next chunk, leaving it empty at the end of the data
+    fn fill_chunk(&mut self) -> io::Result<()> {
+        self.offset += self.chunk.len() as u64;
+        self.chunk.clear();
+        self.chunk_position = 0;
+
+        (&mut self.data)
+            .take(self.bytes_per_checksum as u64)
+            .read_to_end(&mut self.chunk)?;
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+
+        let offset = self.offset;
+        let mismatch =
+            || io::Error::new(io::ErrorKind::InvalidData, Error::ChecksumMismatch(offset));
+        let mut expected = [0; 4];
+        match self.sums.read_exact(&mut expected) {
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(mismatch()),
+            result => result?,
+        }
+
+        if u32::from_be_bytes(expected) != self.checksum_type.checksum(&self.chunk) {
+            return Err(mismatch());
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read, C: Read> Read for ChecksumReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunk_position == self.chunk.len() {
+            self.fill_chunk()?;
+        }
+
+        let available = &self.chunk[self.chunk_position..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.chunk_position += n;
+        Ok(n)
+    }
+}
+
+/// Writes data while producing its `.crc` sidecar, CRC32 over 512 byte chunks unless
+/// configured before the first write. The checksum of the last, partial chunk is written
+/// by `close`, or on drop.
+#[derive(Debug)]
+pub struct ChecksumWriter<W: Write, C: Write> {
+    out: Option<(W, C)>,
+    checksum_type: ChecksumType,
+    bytes_per_checksum: usize,
+    chunk: Vec<u8>,
+    started: bool,
+}
+
+impl ChecksumWriter<BufWriter<File>, BufWriter<File>> {
+    /// Creates a file and its `.crc` sidecar
+    ///
+    /// # Failures
+    /// Returns an `Error` if either file cannot be created
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<ChecksumWriter<BufWriter<File>, BufWriter<File>>> {
+        let path = path.as_ref();
+        let sums = BufWriter::new(File::create(crc_path(path))?);
+        Ok(ChecksumWriter::new(
+            BufWriter::new(File::create(path)?),
+            sums,
+        ))
+    }
+}
+
+impl<W: Write, C: Write> ChecksumWriter<W, C> {
+    /// Wraps a data stream and the stream receiving its `.crc` contents
+    pub fn new(data: W, sums: C) -> ChecksumWriter<W, C> {
+        ChecksumWriter {
+            out: Some((data, sums)),
+            checksum_type: ChecksumType::Crc32,
+            bytes_per_checksum: DEFAULT_BYTES_PER_CHECKSUM,
+            chunk: Vec::new(),
+            started: false,
+        }
+    }
+
+    /// Sets the checksum algorithm, Hadoop's `ChecksumFileSystem` only reads CRC32
+    pub fn checksum_type(mut self, checksum_type: ChecksumType) -> Self {
+        self.checksum_type = checksum_type;
+        self
+    }
+
+    /// Sets the number of data bytes covered by each checksum
+    pub fn bytes_per_checksum(mut self, bytes_per_checksum: usize) -> Self {
+        self.bytes_per_checksum = bytes_per_checksum.clamp(1, i32::MAX as usize);
+        self
+    }
+
+    /// Writes the checksum of the last chunk and flushes, returning both streams
+    pub fn close(mut self) -> Result<(W, C)> {
+        self.finish()?;
+        Ok(self.out.take().expect("writer already closed"))
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.start()?;
+        if !self.chunk.is_empty() {
+            self.write_checksum()?;
+        }
+        let (data, sums) = self.out.as_mut().expect("writer already closed");
+        data.flush()?;
+        sums.flush()
+    }
+
+    // the header is deferred so the settings can be changed after construction
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            let bytes_per_checksum = self.bytes_per_checksum as i32;
+            let (_, sums) = self.out.as_mut().expect("writer already closed");
+            sums.write_all(CHECKSUM_MAGIC)?;
+            sums.write_all(&bytes_per_checksum.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_checksum(&mut self) -> io::Result<()> {
+        let checksum = self.checksum_type.checksum(&self.chunk);
+        self.chunk.clear();
+        let (_, sums) = self.out.as_mut().expect("writer already closed");
+        sums.write_all(&checksum.to_be_bytes())
+    }
+}
+
+impl<W: Write, C: Write> Write for ChecksumWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.start()?;
+        let n = buf.len().min(self.bytes_per_checksum - self.chunk.len());
+        let (data, _) = self.out.as_mut().expect("writer already closed");
+        let n = data.write(&buf[..n])?;
+        self.chunk.extend_from_slice(&buf[..n]);
+        if self.chunk.len() == self.bytes_per_checksum {
+            self.write_checksum()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let (data, sums) = self.out.as_mut().expect("writer already closed");
+        data.flush()?;
+        sums.flush()
+    }
+}
+
+impl<W: Write, C: Write> Drop for ChecksumWriter<W, C> {
+    fn drop(&mut self) {
+        if self.out.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc_path, ChecksumReader, ChecksumType, ChecksumWriter};
+    use crate::errors::Error;
+    use crate::reader::Reader;
+    use crate::tests::long_config;
+    use crate::writer::Writer;
+    use std::io::{Cursor, Read, Write};
+    use std::path::Path;
+
+    fn sidecar(data: &[u8], bytes_per_checksum: usize) -> Vec<u8> {
+        let mut sums = b"crc\0".to_vec();
+        sums.extend_from_slice(&(bytes_per_checksum as i32).to_be_bytes());
+        for chunk in data.chunks(bytes_per_checksum) {
+            let crc = ChecksumType::Crc32.checksum(chunk);
+            sums.extend_from_slice(&crc.to_be_bytes());
+        }
+        sums
+    }
+
+    #[test]
+    fn names_sidecar_files() {
+        assert_eq!(
+            Path::new("/data/.part-00000.crc"),
+            crc_path("/data/part-00000")
+        );
+        assert_eq!(Path::new(".simple.seq.crc"), crc_path("simple.seq"));
+    }
+
+    #[test]
+    fn reads_sequencefile_through_checksums() {
+        let input = ChecksumReader::open("test_data/complex.seq").unwrap();
+        assert_eq!(512, input.bytes_per_checksum());
+
+        let seqfile = Reader::<_, Vec<u8>, Vec<u8>>::new(input).unwrap();
+        let records: Vec<_> = seqfile.map(|kv| kv.unwrap()).collect();
+        assert_eq!(1000, records.len());
+    }
+
+    #[test]
+    fn reports_offset_of_corrupt_chunk() {
+        let data: Vec<u8> = (0..2000u32).map(|i| i as u8).collect();
+        let sums = sidecar(&data, 100);
+
+        let mut clean = Vec::new();
+        ChecksumReader::new(&data[..], &sums[..])
+            .unwrap()
+            .read_to_end(&mut clean)
+            .unwrap();
+        assert_eq!(data, clean);
+
+        let mut corrupt = data.clone();
+        corrupt[1234] ^= 1;
+        let mut input = ChecksumReader::new(&corrupt[..], &sums[..]).unwrap();
+        let mut read = Vec::new();
+        let err = input.read_to_end(&mut read).unwrap_err();
+        match Error::from(err) {
+            Error::ChecksumMismatch(1200) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(1200, read.len());
+
+        // corruption surfaces as the same error through the sequencefile reader
+        let mut file = std::fs::read("test_data/abc_long_text_none.seq").unwrap();
+        let sums = sidecar(&file, 64);
+        file[300] ^= 1;
+        let input = ChecksumReader::new(Cursor::new(file), &sums[..]).unwrap();
+        let err = Reader::<_, i64, crate::Text>::new(input)
+            .and_then(|seqfile| seqfile.collect::<Result<Vec<_>, _>>())
+            .unwrap_err();
+        match err {
+            Error::ChecksumMismatch(256) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_sidecars() {
+        let data = b"some data";
+        assert!(ChecksumReader::new(&data[..], &b"nope\0\0\0\x01"[..]).is_err());
+
+        // sidecar shorter than the data
+        let mut sums = sidecar(data, 4);
+        sums.truncate(sums.len() - 4);
+        let mut read = Vec::new();
+        let err = ChecksumReader::new(&data[..], &sums[..])
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap_err();
+        assert!(matches!(Error::from(err), Error::ChecksumMismatch(8)));
+
+        let sums = sidecar(data, 4);
+        let mut input = ChecksumReader::new(&data[..], &sums[..])
+            .unwrap()
+            .checksum_type(ChecksumType::Crc32c);
+        assert!(input.read_to_end(&mut read).is_err());
+    }
+
+    #[test]
+    fn writes_hadoop_sidecars() {
+        let original = std::fs::read("test_data/abc_long_text_none.seq").unwrap();
+        let mut output = ChecksumWriter::new(Vec::new(), Vec::new());
+        // odd sized writes still produce one checksum per 512 bytes
+        for piece in original.chunks(100) {
+            output.write_all(piece).unwrap();
+        }
+        let (data, sums) = output.close().unwrap();
+
+        assert_eq!(original, data);
+        let expected = std::fs::read("test_data/.abc_long_text_none.seq.crc").unwrap();
+        assert_eq!(expected, sums);
+    }
+
+    #[test]
+    fn round_trips_sequencefile_with_crc32c() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.seq");
+        let output = ChecksumWriter::create(&path)
+            .unwrap()
+            .checksum_type(ChecksumType::Crc32c)
+            .bytes_per_checksum(100);
+        let config = long_config();
+        let mut writer = Writer::<_, i64, i64>::new(output, config).unwrap();
+        for i in 0..1000 {
+            writer.append(&i, &(i * i)).unwrap();
+        }
+        writer.close().unwrap().close().unwrap();
+
+        let input = ChecksumReader::open(&path)
+            .unwrap()
+            .checksum_type(ChecksumType::Crc32c);
+        assert_eq!(100, input.bytes_per_checksum());
+        let records: Vec<_> = Reader::<_, i64, i64>::new(input)
+            .unwrap()
+            .map(|kv| kv.unwrap())
+            .collect();
+        assert_eq!(1000, records.len());
+        assert_eq!((999, 998_001), records[999]);
+
+        // the default CRC32 does not match
+        let input = ChecksumReader::open(&path).unwrap();
+        assert!(Reader::<_, i64, i64>::new(input).is_err());
+        assert!(crc_path(&path).exists());
+    }
+}
@@ -0,0 +1,387 @@
+//! Atomic commit of written files, so readers never see them half-written. An `AtomicFile`
+//! is written under a temporary name and renamed to its final one on `commit`, an
+//! `OutputCommitter` writes `_SUCCESS` once every part file of a directory is committed,
+//! like Hadoop's `FileOutputCommitter` on a local filesystem.
+//!
+//! An `AtomicWriter`, such as the parts of `OutputCommitter::create`, commits its file on
+//! `close`; dropping it unclosed removes what was written. A plain `Writer` of an
+//! `AtomicFile` returns the file uncommitted from `close`, to be committed by the caller.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::commit::OutputCommitter;
+//! # use sequencefile::{Text, WriterConfig};
+//! # fn main() -> sequencefile::Result<()> {
+//! # let config = WriterConfig::new("org.apache.hadoop.io.LongWritable", "org.apache.hadoop.io.Text");
+//! # let partitions: Vec<Vec<(i64, Text)>> = Vec::new();
+//! let committer = OutputCommitter::new("/data/job-output")?;
+//! for (i, records) in partitions.iter().enumerate() {
+//!     let mut writer = committer.create::<i64, Text>(&format!("part-r-{:05}", i), config.clone())?;
+//!     for (key, value) in records {
+//!         writer.append(key, value)?;
+//!     }
+//!     writer.close()?;
+//! }
+//! committer.commit()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::directory::SUCCESS_FILE_NAME;
+use crate::errors::{Error, Result};
+use crate::writable::Writable;
+use crate::writer::{Syncable, Writer, WriterConfig};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+/// Name of the directory holding files until they are committed
+pub const TEMPORARY_DIR_NAME: &str = "_temporary";
+
+/// Where a file is written until it is committed, both are skipped by `DirectoryReader`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempLocation {
+    /// `.<name>.tmp` next to the final file
+    Hidden,
+    /// `_temporary/<name>` in the final file's directory, like Hadoop's task outputs
+    TemporaryDir,
+}
+
+impl TempLocation {
+    /// Temporary path of a file to be committed as `path`
+    pub fn temp_path(self, path: &Path) -> PathBuf {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let name = path.file_name().unwrap_or_default();
+        match self {
+            TempLocation::Hidden => {
+                let mut hidden = OsString::from(".");
+                hidden.push(name);
+                hidden.push(".tmp");
+                dir.join(hidden)
+            }
+            TempLocation::TemporaryDir => dir.join(TEMPORARY_DIR_NAME).join(name),
+        }
+    }
+}
+
+/// File written under a temporary name until `commit` renames it to its final one.
+/// Dropping it uncommitted removes the temporary file.
+#[derive(Debug)]
+#[must_use = "an uncommitted file is removed when dropped"]
+pub struct AtomicFile {
+    file: Option<File>,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file of `path`, replacing a stale one
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if the file cannot be created
+    pub fn create<P: AsRef<Path>>(path: P, location: TempLocation) -> Result<AtomicFile> {
+        let path = path.as_ref().to_path_buf();
+        let temp_path = location.temp_path(&path);
+        if let Some(dir) = temp_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(AtomicFile {
+            file: Some(File::create(&temp_path)?),
+            temp_path,
+            path,
+        })
+    }
+
+    /// Final path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path the file is written to until it is committed
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Syncs the file to disk and renames it to its final path, replacing any file there
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if the file cannot be synced or renamed
+    pub fn commit(mut self) -> Result<PathBuf> {
+        let file = self.file.take().expect("file already committed");
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&self.temp_path, &self.path)?;
+        sync_dir(&self.path);
+        Ok(self.path.clone())
+    }
+}
+
+impl<K: Writable, V: Writable> Writer<AtomicFile, K, V> {
+    /// Closes the writer and commits its file, returning the final path
+    ///
+    /// # Failures
+    /// Returns an `Error` if the writer cannot be closed or the file committed
+    pub fn commit(self) -> Result<PathBuf> {
+        self.close()?.commit()
+    }
+}
+
+/// Writer of an `AtomicFile` which commits it on `close`, dereferencing to the `Writer`.
+/// Dropping it unclosed removes the temporary file.
+#[derive(Debug)]
+#[must_use = "the file is only committed by `close`"]
+pub struct AtomicWriter<K: Writable, V: Writable> {
+    writer: Writer<AtomicFile, K, V>,
+}
+
+impl<K: Writable, V: Writable> AtomicWriter<K, V> {
+    /// Creates the temporary file of `path` and writes its header
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file or its header cannot be written
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        location: TempLocation,
+        config: WriterConfig,
+    ) -> Result<AtomicWriter<K, V>> {
+        let file = AtomicFile::create(path, location)?;
+        Ok(AtomicWriter {
+            writer: Writer::new(file, config)?,
+        })
+    }
+
+    /// Closes the writer and commits its file, returning the final path
+    ///
+    /// # Failures
+    /// Returns an `Error` if the writer cannot be closed or the file committed, the
+    /// temporary file is then removed
+    pub fn close(self) -> Result<PathBuf> {
+        self.writer.commit()
+    }
+}
+
+impl<K: Writable, V: Writable> Deref for AtomicWriter<K, V> {
+    type Target = Writer<AtomicFile, K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.writer
+    }
+}
+
+impl<K: Writable, V: Writable> DerefMut for AtomicWriter<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.writer
+    }
+}
+
+impl io::Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.as_mut().expect("file committed").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().expect("file committed").flush()
+    }
+}
+
+impl Syncable for AtomicFile {
+    fn hsync(&mut self) -> io::Result<()> {
+        self.file.as_mut().expect("file committed").sync_data()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Commits the part files of an output directory as a whole: parts are written under
+/// `_temporary/` and moved into the directory as each is committed, `commit` then removes
+/// `_temporary/` and writes `_SUCCESS`
+#[derive(Debug)]
+pub struct OutputCommitter {
+    dir: PathBuf,
+}
+
+impl OutputCommitter {
+    /// Committer of the output directory `dir`, creating it if needed
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if the directory cannot be created
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<OutputCommitter> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(TEMPORARY_DIR_NAME))?;
+        Ok(OutputCommitter { dir })
+    }
+
+    /// Output directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Creates a part file `name` under `_temporary/`, moved into the directory when the
+    /// writer is closed
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file or its header cannot be written
+    pub fn create<K: Writable, V: Writable>(
+        &self,
+        name: &str,
+        config: WriterConfig,
+    ) -> Result<AtomicWriter<K, V>> {
+        AtomicWriter::create(self.dir.join(name), TempLocation::TemporaryDir, config)
+    }
+
+    /// Marks the output complete by writing `_SUCCESS`, after removing `_temporary/`
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if a part file was not committed or the directory cannot be updated
+    pub fn commit(&self) -> Result<()> {
+        let temporary = self.dir.join(TEMPORARY_DIR_NAME);
+        if temporary.exists() {
+            let pending: Vec<_> = fs::read_dir(&temporary)?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect::<io::Result<_>>()?;
+            if !pending.is_empty() {
+                return Err(Error::IO(io::Error::other(format!(
+                    "uncommitted files in {}: {:?}",
+                    temporary.display(),
+                    pending
+                ))));
+            }
+            fs::remove_dir(&temporary)?;
+        }
+
+        File::create(self.dir.join(SUCCESS_FILE_NAME))?.sync_all()?;
+        sync_dir(&self.dir.join(SUCCESS_FILE_NAME));
+        Ok(())
+    }
+
+    /// Discards the output of uncommitted part files
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if `_temporary/` cannot be removed
+    pub fn abort(self) -> Result<()> {
+        let temporary = self.dir.join(TEMPORARY_DIR_NAME);
+        if temporary.exists() {
+            fs::remove_dir_all(temporary)?;
+        }
+        Ok(())
+    }
+}
+
+// best effort sync of the directory entry of `path`, not supported on every platform
+fn sync_dir(path: &Path) {
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicFile, AtomicWriter, OutputCommitter, TempLocation};
+    use crate::directory::DirectoryReader;
+    use crate::errors::Error;
+    use crate::reader::Reader;
+    use crate::tests::test_config;
+    use crate::Text;
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile;
+
+    #[test]
+    fn renames_on_commit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert_eq!(
+            dir.join(".part-00000.tmp"),
+            TempLocation::Hidden.temp_path(&dir.join("part-00000"))
+        );
+        assert_eq!(
+            Path::new("out/_temporary/part-00000"),
+            TempLocation::TemporaryDir.temp_path(Path::new("out/part-00000"))
+        );
+
+        let mut file = AtomicFile::create(dir.join("a"), TempLocation::Hidden).unwrap();
+        file.write_all(b"data").unwrap();
+        assert!(!dir.join("a").exists());
+        assert_eq!(dir.join("a"), file.commit().unwrap());
+        assert_eq!(b"data", &fs::read(dir.join("a")).unwrap()[..]);
+
+        let mut file = AtomicFile::create(dir.join("b"), TempLocation::TemporaryDir).unwrap();
+        file.write_all(b"data").unwrap();
+        let temp_path = file.temp_path().to_path_buf();
+        assert!(temp_path.exists());
+        drop(file);
+        assert!(!temp_path.exists());
+        assert!(!dir.join("b").exists());
+    }
+
+    #[test]
+    fn commits_on_close() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("a.seq");
+
+        let mut writer =
+            AtomicWriter::<i64, Text>::create(&path, TempLocation::Hidden, test_config()).unwrap();
+        writer.append(&1, &Text::from("one")).unwrap();
+        let temp_path = TempLocation::Hidden.temp_path(&path);
+        assert!(!path.exists());
+        assert_eq!(path, writer.close().unwrap());
+        assert!(!temp_path.exists());
+        let reader = Reader::<_, i64, Text>::new(fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(1, reader.count());
+
+        let writer =
+            AtomicWriter::<i64, Text>::create(&path, TempLocation::Hidden, test_config()).unwrap();
+        drop(writer);
+        assert!(!temp_path.exists());
+        // the committed file is left as is
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn writes_success_after_parts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let config = test_config();
+        let committer = OutputCommitter::new(dir).unwrap();
+        for part in 0..2 {
+            let mut writer = committer
+                .create::<i64, Text>(&format!("part-r-{:05}", part), config.clone())
+                .unwrap();
+            writer.append(&part, &Text::from("value")).unwrap();
+            assert_eq!(
+                dir.join(format!("part-r-{:05}", part)),
+                writer.close().unwrap()
+            );
+        }
+        let pending = committer
+            .create::<i64, Text>("part-r-00002", config.clone())
+            .unwrap();
+        assert!(DirectoryReader::<i64, Text>::open_committed(dir).is_err());
+
+        match committer.commit() {
+            Err(Error::IO(ref e)) if e.to_string().contains("part-r-00002") => (),
+            other => panic!("expected an uncommitted part, got {:?}", other),
+        }
+        // dropped unclosed
+        drop(pending);
+        committer.commit().unwrap();
+
+        let keys: Vec<i64> = DirectoryReader::<i64, Text>::open_committed(dir)
+            .unwrap()
+            .map(|record| record.unwrap().1)
+            .collect();
+        assert_eq!(vec![0, 1], keys);
+        assert!(!dir.join("_temporary").exists());
+    }
+}
@@ -1,11 +1,17 @@
-use bzip2::reader::BzDecompressor;
-use errors::Result;
-use flate2::read::{GzDecoder, ZlibDecoder};
+use crate::errors::{Error, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bzip2::reader::{BzCompressor, BzDecompressor};
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
 use std::io;
 
 pub const DEFAULT_CODEC: &str = "org.apache.hadoop.io.compress.DefaultCodec";
 pub const GZIP_CODEC: &str = "org.apache.hadoop.io.compress.GzipCodec";
 pub const BZIP2_CODEC: &str = "org.apache.hadoop.io.compress.BZip2Codec";
+pub const SNAPPY_CODEC: &str = "org.apache.hadoop.io.compress.SnappyCodec";
+
+// io.compression.codec.snappy.buffersize
+const SNAPPY_BUFFER_SIZE: usize = 256 * 1024;
 
 /// Type of compression used on the sequencefile.
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -28,6 +34,15 @@ pub enum Codec {
     Gzip,
     /// Bzip2 compression
     Bzip2,
+    /// Snappy, in Hadoop's block stream framing
+    Snappy,
+}
+
+impl Codec {
+    /// Fully-qualified Java class of the codec
+    pub fn class_name(&self) -> &'static str {
+        codec_class(self)
+    }
 }
 
 pub fn codec(codec: &str) -> Option<Codec> {
@@ -35,21 +50,85 @@ pub fn codec(codec: &str) -> Option<Codec> {
         DEFAULT_CODEC => Some(Codec::Default),
         GZIP_CODEC => Some(Codec::Gzip),
         BZIP2_CODEC => Some(Codec::Bzip2),
+        SNAPPY_CODEC => Some(Codec::Snappy),
         _ => None,
     }
 }
 
+/// Fully-qualified Java class of the codec, as written in the header
+pub fn codec_class(codec: &Codec) -> &'static str {
+    match *codec {
+        Codec::Default => DEFAULT_CODEC,
+        Codec::Gzip => GZIP_CODEC,
+        Codec::Bzip2 => BZIP2_CODEC,
+        Codec::Snappy => SNAPPY_CODEC,
+    }
+}
+
+pub fn compressor(codec: &Codec, buffer: &[u8]) -> Result<Vec<u8>> {
+    match *codec {
+        Codec::Default => read_all(&mut ZlibEncoder::new(buffer, Compression::Default)),
+        Codec::Gzip => read_all(&mut GzEncoder::new(buffer, Compression::Default)),
+        Codec::Bzip2 => read_all(&mut BzCompressor::new(buffer, bzip2::Compress::Default)),
+        Codec::Snappy => snappy_compress(buffer),
+    }
+}
+
 pub fn decompressor(codec: &Codec, buffer: &[u8]) -> Result<Vec<u8>> {
     match *codec {
-        Codec::Default => decompress(&mut ZlibDecoder::new(buffer)),
-        Codec::Gzip => decompress(&mut GzDecoder::new(buffer)?),
-        Codec::Bzip2 => decompress(&mut BzDecompressor::new(buffer)),
+        Codec::Default => read_all(&mut ZlibDecoder::new(buffer)),
+        Codec::Gzip => read_all(&mut GzDecoder::new(buffer)?),
+        Codec::Bzip2 => read_all(&mut BzDecompressor::new(buffer)),
+        Codec::Snappy => snappy_decompress(buffer),
+    }
+}
+
+// Hadoop's BlockCompressorStream: each chunk is an i32 uncompressed length followed by
+// the i32 length and bytes of its raw snappy compression
+fn snappy_compress(buffer: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = snap::raw::Encoder::new();
+    let mut out = Vec::new();
+    for chunk in buffer.chunks(SNAPPY_BUFFER_SIZE) {
+        let compressed = encoder
+            .compress_vec(chunk)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+        out.write_i32::<BigEndian>(chunk.len() as i32)?;
+        out.write_i32::<BigEndian>(compressed.len() as i32)?;
+        out.extend_from_slice(&compressed);
+    }
+    Ok(out)
+}
+
+// BlockDecompressorStream, an uncompressed length may be split over several compressed chunks
+fn snappy_decompress(buffer: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = snap::raw::Decoder::new();
+    let mut input = io::Cursor::new(buffer);
+    let mut out = Vec::new();
+    while (input.position() as usize) < buffer.len() {
+        let block_size = input.read_i32::<BigEndian>()?.max(0) as usize;
+        let block_end = out.len() + block_size;
+        while out.len() < block_end {
+            let length = input.read_i32::<BigEndian>()?.max(0) as usize;
+            let start = input.position() as usize;
+            let compressed = buffer.get(start..start + length).ok_or_else(|| {
+                Error::IO(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated snappy chunk",
+                ))
+            })?;
+            input.set_position((start + length) as u64);
+            let decompressed = decoder
+                .decompress_vec(compressed)
+                .map_err(|e| Error::Serialization(e.to_string()))?;
+            out.extend_from_slice(&decompressed);
+        }
     }
+    Ok(out)
 }
 
-fn decompress<R: io::Read>(decompressor: &mut R) -> Result<Vec<u8>> {
+fn read_all<R: io::Read>(stream: &mut R) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
-    decompressor.read_to_end(&mut buf)?;
+    stream.read_to_end(&mut buf)?;
 
     Ok(buf)
 }
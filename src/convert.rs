@@ -0,0 +1,154 @@
+//! Rewriting a sequencefile with another compression type or codec. Records are streamed
+//! as raw bytes, so any key and value classes can be converted.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::convert::Converter;
+//! # use sequencefile::{Codec, CompressionType};
+//! # use std::fs::File;
+//! # fn main() -> sequencefile::Result<()> {
+//! // bzip2 record compressed archive into a snappy block compressed file
+//! let output = Converter::new()
+//!     .compression(CompressionType::Block, Codec::Snappy)
+//!     .metadata("converted.from", "archive.seq")
+//!     .convert(File::open("archive.seq")?, File::create("archive-snappy.seq")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::compress::{Codec, CompressionType};
+use crate::errors::Result;
+use crate::reader::Reader;
+use crate::writer::{Writer, WriterConfig, DEFAULT_BLOCK_SIZE};
+use std::collections::HashMap;
+use std::io;
+
+/// Copies records into a new file, keeping the header's classes and metadata
+#[derive(Debug, Clone)]
+pub struct Converter {
+    compression: Option<(CompressionType, Codec)>,
+    metadata: HashMap<String, String>,
+    block_size: usize,
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Converter::new()
+    }
+}
+
+impl Converter {
+    /// Converter keeping the input's compression, useful to only change metadata
+    pub fn new() -> Converter {
+        Converter {
+            compression: None,
+            metadata: HashMap::new(),
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+
+    /// Sets the output compression type and codec
+    pub fn compression(mut self, compression_type: CompressionType, codec: Codec) -> Self {
+        self.compression = Some((compression_type, codec));
+        self
+    }
+
+    /// Adds or overrides a metadata entry
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the uncompressed block size of block compressed output
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Rewrites `input` into `output`, returning the underlying io::Write
+    ///
+    /// # Failures
+    /// Returns an `Error` if the input is malformed or the output cannot be written
+    pub fn convert<R: io::Read, W: io::Write>(&self, input: R, output: W) -> Result<W> {
+        let reader = Reader::<R, Vec<u8>, Vec<u8>>::new(input)?;
+
+        let mut config = WriterConfig::from_header(&reader.header).block_size(self.block_size);
+        if let Some((compression_type, codec)) = self.compression {
+            config = config.compression(compression_type, codec);
+        }
+        for (key, value) in &self.metadata {
+            config = config.metadata(key, value);
+        }
+
+        let mut writer = Writer::<W, Vec<u8>, Vec<u8>>::new(output, config)?;
+        for kv in reader {
+            let (key, value) = kv?;
+            writer.append_raw(&key, &value)?;
+        }
+        writer.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Converter;
+    use crate::reader::Reader;
+    use crate::{Codec, CompressionType, Text};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    fn records(file: &[u8]) -> Vec<(i64, String)> {
+        Reader::<_, i64, Text>::new(Cursor::new(file))
+            .unwrap()
+            .map(|kv| kv.unwrap())
+            .map(|(k, v)| (k, v.to_string().into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn converts_between_codecs() {
+        let original = std::fs::read("test_data/abc_long_text_bzip2_record.seq").unwrap();
+        let expected = records(&original);
+
+        for &(compression_type, codec) in &[
+            (CompressionType::Block, Codec::Snappy),
+            (CompressionType::Record, Codec::Gzip),
+            (CompressionType::None, Codec::Default),
+        ] {
+            let converted = Converter::new()
+                .compression(compression_type, codec)
+                .convert(
+                    File::open("test_data/abc_long_text_bzip2_record.seq").unwrap(),
+                    Vec::new(),
+                )
+                .unwrap();
+
+            let reader = Reader::<_, i64, Text>::new(Cursor::new(&converted)).unwrap();
+            assert_eq!(compression_type, reader.header.compression_type);
+            assert_eq!("org.apache.hadoop.io.LongWritable", reader.header.key_class);
+            assert_eq!(expected, records(&converted));
+        }
+    }
+
+    #[test]
+    fn keeps_and_overrides_metadata() {
+        let converted = Converter::new()
+            .metadata("a", "overridden")
+            .metadata("added", "yes")
+            .convert(File::open("test_data/metadata.seq").unwrap(), Vec::new())
+            .unwrap();
+
+        let reader = Reader::<_, Vec<u8>, Vec<u8>>::new(Cursor::new(converted)).unwrap();
+        let original =
+            Reader::<_, Vec<u8>, Vec<u8>>::new(File::open("test_data/metadata.seq").unwrap())
+                .unwrap();
+        assert_eq!(
+            original.header.compression_type,
+            reader.header.compression_type
+        );
+        assert_eq!("overridden", reader.header.metadata["a"]);
+        assert_eq!("z", reader.header.metadata["y"]);
+        assert_eq!("yes", reader.header.metadata["added"]);
+        assert_eq!(original.count(), reader.count());
+    }
+}
@@ -0,0 +1,377 @@
+//! Querying sequencefiles with DataFusion. `SequenceFileTable` is a `TableProvider` over a file
+//! or the part files of a directory, with `key` and `value` columns typed after the header's
+//! classes (see `arrow::data_type`). Files are scanned in splits starting at sync markers, one
+//! partition per split, and only the projected columns are decoded.
+//!
+//! Each partition reads its records on a Tokio blocking thread, sending batches to the
+//! partition's stream, so file I/O and decompression don't stall the runtime's workers.
+//!
+//! There is no `FileFormat` for `ListingTable`s yet, tables are registered as providers.
+//!
+//! # Example
+//! ```no_run
+//! # use datafusion::prelude::{col, lit, SessionContext};
+//! # use sequencefile::datafusion::SequenceFileTable;
+//! # use std::sync::Arc;
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let ctx = SessionContext::new();
+//! ctx.register_table("events", Arc::new(SequenceFileTable::open("/data/events")?))?;
+//! let df = ctx
+//!     .table("events")
+//!     .await?
+//!     .filter(col("key").gt(lit(1000i64)))?
+//!     .select_columns(&["value"])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::arrow::{BatchReader, ColumnMapper, Skip, WritableColumn, DEFAULT_BATCH_SIZE};
+use crate::datafusion_crate::catalog::{Session, TableProvider};
+use crate::datafusion_crate::error::{DataFusionError, Result as DataFusionResult};
+use crate::datafusion_crate::execution::{SendableRecordBatchStream, TaskContext};
+use crate::datafusion_crate::logical_expr::{Expr, TableType};
+use crate::datafusion_crate::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use crate::datafusion_crate::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use crate::datafusion_crate::physical_plan::ExecutionPlan;
+use crate::errors::{Error, Result};
+use crate::reader::Reader;
+use crate::util::part_files;
+use crate::Header;
+use arrow_schema::{Schema, SchemaRef};
+use async_trait::async_trait;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default number of bytes of a file read by one partition
+pub const DEFAULT_SPLIT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Table of one or more sequencefiles sharing key and value classes
+#[derive(Debug, Clone)]
+pub struct SequenceFileTable {
+    files: Vec<(PathBuf, u64)>,
+    header: Header,
+    schema: SchemaRef,
+    split_size: u64,
+    batch_size: usize,
+}
+
+impl SequenceFileTable {
+    /// Table of a single file, or of the part files of a directory in name order. Hidden
+    /// files and files starting with `_`, e.g. `_SUCCESS`, are skipped.
+    ///
+    /// # Failures
+    /// Returns `Error::IncompatibleHeader` if the directory has no part files or they have
+    /// different key or value classes
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SequenceFileTable> {
+        let path = path.as_ref();
+        let paths = if path.is_dir() {
+            part_files(path)?
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut header: Option<Header> = None;
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let file = File::open(&path)?;
+            let len = file.metadata()?.len();
+            let file_header = Reader::<_, Vec<u8>, Vec<u8>>::new(file)?.header;
+            match header {
+                Some(ref header)
+                    if header.key_class != file_header.key_class
+                        || header.value_class != file_header.value_class =>
+                {
+                    return Err(Error::IncompatibleHeader(format!(
+                        "expected {}/{}, found {}/{} in {}",
+                        header.key_class,
+                        header.value_class,
+                        file_header.key_class,
+                        file_header.value_class,
+                        path.display()
+                    )));
+                }
+                Some(_) => (),
+                None => header = Some(file_header),
+            }
+            files.push((path, len));
+        }
+
+        let header = match header {
+            Some(header) => header,
+            None => {
+                return Err(Error::IncompatibleHeader(format!(
+                    "no part files in {}",
+                    path.display()
+                )))
+            }
+        };
+        let mut fields = key_column(&header).fields();
+        fields.extend(value_column(&header).fields());
+
+        Ok(SequenceFileTable {
+            files,
+            header,
+            schema: Arc::new(Schema::new(fields)),
+            split_size: DEFAULT_SPLIT_SIZE,
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    /// Sets the number of bytes of a file read by one partition
+    pub fn split_size(mut self, split_size: u64) -> Self {
+        self.split_size = split_size.max(1);
+        self
+    }
+
+    /// Sets the maximum number of records per batch
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Header of the first file
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Number of partitions a scan reads
+    pub fn splits(&self) -> usize {
+        self.files
+            .iter()
+            .map(|&(_, len)| split_count(len, self.split_size) as usize)
+            .sum()
+    }
+
+    fn plan(
+        &self,
+        projection: Option<&Vec<usize>>,
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let all = vec![0, 1];
+        let projection = projection.unwrap_or(&all);
+        let keys = projection.contains(&0);
+        let values = projection.contains(&1);
+
+        // splits decode the projected columns in schema order, the exec reorders them
+        let decoded: Vec<usize> = [0, 1]
+            .iter()
+            .cloned()
+            .filter(|i| projection.contains(i))
+            .collect();
+        let schema = Arc::new(self.schema.project(&decoded)?);
+        let reorder: Vec<usize> = projection
+            .iter()
+            .map(|i| decoded.iter().position(|d| d == i).unwrap_or(0))
+            .collect();
+
+        let mut partitions: Vec<Arc<dyn PartitionStream>> = Vec::new();
+        for &(ref path, len) in &self.files {
+            for i in 0..split_count(len, self.split_size) {
+                let start = i * self.split_size;
+                partitions.push(Arc::new(Split {
+                    path: path.clone(),
+                    start,
+                    end: (start + self.split_size).min(len),
+                    schema: schema.clone(),
+                    keys,
+                    values,
+                    batch_size: self.batch_size,
+                }));
+            }
+        }
+
+        let exec =
+            StreamingTableExec::try_new(schema, partitions, Some(&reorder), vec![], false, limit)?;
+        Ok(Arc::new(exec))
+    }
+}
+
+#[async_trait]
+impl TableProvider for SequenceFileTable {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    // planning itself doesn't wait on anything
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.plan(projection, limit)
+    }
+}
+
+// byte range of a file read by one partition
+#[derive(Debug, Clone)]
+struct Split {
+    path: PathBuf,
+    start: u64,
+    end: u64,
+    schema: SchemaRef,
+    keys: bool,
+    values: bool,
+    batch_size: usize,
+}
+
+impl Split {
+    fn batches(&self) -> Result<BatchReader<File>> {
+        let mut reader = Reader::new(File::open(&self.path)?)?;
+        reader.split(self.start, self.end)?;
+
+        let mut batches = BatchReader::new(reader).batch_size(self.batch_size);
+        if !self.keys {
+            batches = batches.key_columns(Skip);
+        }
+        if !self.values {
+            batches = batches.value_columns(Skip);
+        }
+        Ok(batches)
+    }
+}
+
+impl PartitionStream for Split {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 2);
+        let tx = builder.tx();
+        let split = self.clone();
+        builder.spawn_blocking(move || {
+            let external = |e: Error| DataFusionError::External(Box::new(e));
+            for batch in split.batches().map_err(external)? {
+                // the stream was dropped, e.g. once a limit is reached
+                if tx.blocking_send(batch.map_err(external)).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+fn key_column(header: &Header) -> WritableColumn {
+    WritableColumn::new("key", &header.key_class)
+}
+
+fn value_column(header: &Header) -> WritableColumn {
+    WritableColumn::new("value", &header.value_class)
+}
+
+fn split_count(len: u64, split_size: u64) -> u64 {
+    len.div_ceil(split_size).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SequenceFileTable;
+    use crate::datafusion_crate::catalog::TableProvider;
+    use crate::datafusion_crate::physical_plan::collect;
+    use crate::datafusion_crate::prelude::SessionContext;
+    use crate::tests::test_config;
+    use crate::writer::Writer;
+    use crate::{Codec, CompressionType, Text};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use arrow_array::RecordBatch;
+    use std::fs::File;
+    use std::sync::Arc;
+    use tokio::runtime::Runtime;
+
+    fn keys(batches: &[RecordBatch], column: usize) -> Vec<i64> {
+        let mut keys: Vec<i64> = batches
+            .iter()
+            .flat_map(|b| {
+                b.column(column)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn scans_splits_of_part_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for (i, &compression) in [CompressionType::Record, CompressionType::Block]
+            .iter()
+            .enumerate()
+        {
+            let config = test_config()
+                .compression(compression, Codec::Snappy)
+                .block_size(2048)
+                .sync_interval(512);
+            let file = File::create(dir.join(format!("part-r-0000{}", i))).unwrap();
+            let mut writer = Writer::<_, i64, Text>::new(file, config).unwrap();
+            for key in 0..1000 {
+                writer
+                    .append(
+                        &(i as i64 * 1000 + key),
+                        &Text::from(format!("value-{}", key)),
+                    )
+                    .unwrap();
+            }
+            writer.close().unwrap();
+        }
+        File::create(dir.join("_SUCCESS")).unwrap();
+
+        let table = Arc::new(SequenceFileTable::open(dir).unwrap().split_size(4096));
+        assert!(table.splits() > 4);
+        let rt = Runtime::new().unwrap();
+        let ctx = SessionContext::new();
+        let df = ctx.read_table(table.clone()).unwrap();
+        let batches = rt.block_on(df.clone().collect()).unwrap();
+
+        assert_eq!((0..2000).collect::<Vec<_>>(), keys(&batches, 0));
+        let values = df.select_columns(&["value"]).unwrap();
+        assert_eq!(1, values.schema().fields().len());
+
+        // errors of the blocking reads reach the stream
+        std::fs::remove_file(dir.join("part-r-00001")).unwrap();
+        let df = ctx.read_table(table).unwrap();
+        assert!(rt.block_on(df.collect()).is_err());
+    }
+
+    #[test]
+    fn projects_columns() {
+        let table = SequenceFileTable::open("test_data/abc_long_text_gzip_block.seq").unwrap();
+        assert_eq!("key", table.schema().field(0).name());
+
+        let rt = Runtime::new().unwrap();
+        let ctx = SessionContext::new();
+        let state = ctx.state();
+
+        let plan = rt
+            .block_on(table.scan(&state, Some(&vec![1, 0]), &[], None))
+            .unwrap();
+        let batches = rt.block_on(collect(plan, ctx.task_ctx())).unwrap();
+        assert_eq!("value", batches[0].schema().field(0).name());
+        assert_eq!((0..26).collect::<Vec<_>>(), keys(&batches, 1));
+        assert_eq!("\0A", batches[0].column(0).as_string::<i32>().value(0));
+
+        let plan = rt
+            .block_on(table.scan(&state, Some(&vec![]), &[], Some(5)))
+            .unwrap();
+        let batches = rt.block_on(collect(plan, ctx.task_ctx())).unwrap();
+        assert_eq!(0, batches[0].num_columns());
+        assert_eq!(5, batches.iter().map(|b| b.num_rows()).sum::<usize>());
+
+        let count = rt
+            .block_on(ctx.read_table(Arc::new(table)).unwrap().count())
+            .unwrap();
+        assert_eq!(26, count);
+    }
+}
@@ -0,0 +1,231 @@
+//! Reading Hadoop job output directories, the `part-r-NNNNN` files of a job read back to back
+//! as one stream of records. Hidden files, e.g. `.part-r-00000.crc`, and files starting with
+//! `_`, e.g. `_SUCCESS` or `_logs`, are skipped.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::directory::DirectoryReader;
+//! # use sequencefile::Text;
+//! # fn main() -> sequencefile::Result<()> {
+//! // fails unless the job committed its output
+//! let reader = DirectoryReader::<i64, Text>::open_committed("/data/job-output")?;
+//! for record in reader {
+//!     let (path, key, value) = record?;
+//!     println!("{}: {:?} {:?}", path.display(), key, value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::reader::Reader;
+use crate::util::part_files;
+use crate::writable::Writable;
+use crate::Header;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Name of the marker file a job writes to its output directory once it has committed
+pub const SUCCESS_FILE_NAME: &str = "_SUCCESS";
+
+/// Reads the part files of a directory in name order, tagging each record with its file
+#[derive(Debug)]
+pub struct DirectoryReader<K: Writable, V: Writable> {
+    paths: Vec<Arc<Path>>,
+    header: Header,
+    current: Option<(Arc<Path>, Reader<File, K, V>)>,
+    next_file: usize,
+    is_error: bool,
+}
+
+impl<K: Writable, V: Writable> DirectoryReader<K, V> {
+    /// Reader of the part files of `dir`, whether or not the job committed
+    ///
+    /// # Failures
+    /// Returns `Error::IncompatibleHeader` if the directory has no part files or they differ
+    /// in key class, value class, compression type or codec
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<DirectoryReader<K, V>> {
+        let dir = dir.as_ref();
+        let paths = part_files(dir)?;
+
+        let mut header: Option<Header> = None;
+        for path in &paths {
+            let file_header = Reader::<_, K, V>::new(File::open(path)?)?.header;
+            match header {
+                Some(ref header) => check_compatible(header, &file_header, path)?,
+                None => header = Some(file_header),
+            }
+        }
+
+        let header = match header {
+            Some(header) => header,
+            None => {
+                return Err(Error::IncompatibleHeader(format!(
+                    "no part files in {}",
+                    dir.display()
+                )))
+            }
+        };
+
+        Ok(DirectoryReader {
+            paths: paths.into_iter().map(Arc::from).collect(),
+            header,
+            current: None,
+            next_file: 0,
+            is_error: false,
+        })
+    }
+
+    /// Reader of the part files of `dir`, requiring the `_SUCCESS` marker of a committed job
+    ///
+    /// # Failures
+    /// Returns `Error::IO` with `io::ErrorKind::NotFound` if `_SUCCESS` is missing, otherwise
+    /// as `open`
+    pub fn open_committed<P: AsRef<Path>>(dir: P) -> Result<DirectoryReader<K, V>> {
+        let dir = dir.as_ref();
+        if !dir.join(SUCCESS_FILE_NAME).is_file() {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no {} in {}", SUCCESS_FILE_NAME, dir.display()),
+            )));
+        }
+        DirectoryReader::open(dir)
+    }
+
+    /// Header of the first part file, the others agree on classes and compression
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Part files in the order they are read
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.paths.iter().map(|p| p.to_path_buf()).collect()
+    }
+
+    fn next_record(&mut self) -> Result<Option<(Arc<Path>, K, V)>> {
+        loop {
+            if let Some((ref path, ref mut reader)) = self.current {
+                if let Some(kv) = reader.next() {
+                    let (key, value) = kv?;
+                    return Ok(Some((path.clone(), key, value)));
+                }
+            }
+
+            let path = match self.paths.get(self.next_file) {
+                Some(path) => path.clone(),
+                None => return Ok(None),
+            };
+            self.next_file += 1;
+            let reader = Reader::new(File::open(&path)?)?;
+            // a file replaced since `open`
+            check_compatible(&self.header, &reader.header, &path)?;
+            self.current = Some((path, reader));
+        }
+    }
+}
+
+impl<K: Writable, V: Writable> Iterator for DirectoryReader<K, V> {
+    type Item = Result<(Arc<Path>, K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_error {
+            return None;
+        }
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => {
+                self.is_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn check_compatible(expected: &Header, found: &Header, path: &Path) -> Result<()> {
+    if expected.key_class != found.key_class || expected.value_class != found.value_class {
+        return Err(Error::IncompatibleHeader(format!(
+            "expected {}/{}, found {}/{} in {}",
+            expected.key_class,
+            expected.value_class,
+            found.key_class,
+            found.value_class,
+            path.display()
+        )));
+    }
+    if expected.compression_type != found.compression_type
+        || expected.compression_codec != found.compression_codec
+    {
+        return Err(Error::IncompatibleHeader(format!(
+            "expected {:?} compression with {:?}, found {:?} with {:?} in {}",
+            expected.compression_type,
+            expected.compression_codec,
+            found.compression_type,
+            found.compression_codec,
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectoryReader, SUCCESS_FILE_NAME};
+    use crate::errors::Error;
+    use crate::tests::test_config;
+    use crate::writer::Writer;
+    use crate::{Codec, CompressionType, Text};
+    use std::fs::{self, File};
+    use tempfile::{self, TempDir};
+
+    fn job_output(codecs: &[Codec]) -> TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("_logs")).unwrap();
+        for (i, &codec) in codecs.iter().enumerate() {
+            let config = test_config().compression(CompressionType::Block, codec);
+            let file = File::create(dir.join(format!("part-r-{:05}", i))).unwrap();
+            let mut writer = Writer::<_, i64, Text>::new(file, config).unwrap();
+            for key in 0..10 {
+                writer
+                    .append(&(i as i64 * 10 + key), &Text::from(format!("v{}", key)))
+                    .unwrap();
+            }
+            writer.close().unwrap();
+        }
+        fs::write(dir.join(".part-r-00000.crc"), b"crc").unwrap();
+        tmp
+    }
+
+    #[test]
+    fn reads_part_files_in_order() {
+        let tmp = job_output(&[Codec::Gzip, Codec::Gzip, Codec::Gzip]);
+        let dir = tmp.path();
+        match DirectoryReader::<i64, Text>::open_committed(dir) {
+            Err(Error::IO(ref e)) => assert_eq!(::std::io::ErrorKind::NotFound, e.kind()),
+            other => panic!("expected a missing _SUCCESS, got {:?}", other.map(|_| ())),
+        }
+        File::create(dir.join(SUCCESS_FILE_NAME)).unwrap();
+
+        let reader = DirectoryReader::<i64, Text>::open_committed(dir).unwrap();
+        assert_eq!(3, reader.paths().len());
+        let records: Vec<_> = reader.map(|r| r.unwrap()).collect();
+
+        assert_eq!(
+            (0..30).collect::<Vec<_>>(),
+            records.iter().map(|r| r.1).collect::<Vec<_>>()
+        );
+        assert_eq!(dir.join("part-r-00001"), records[10].0.to_path_buf());
+        assert_eq!("v9", records[29].2.to_string());
+    }
+
+    #[test]
+    fn rejects_mixed_codecs() {
+        let dir = job_output(&[Codec::Gzip, Codec::Snappy]);
+        match DirectoryReader::<i64, Text>::open(dir.path()) {
+            Err(Error::IncompatibleHeader(m)) => assert!(m.contains("part-r-00001"), "{}", m),
+            other => panic!("expected incompatible headers, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -30,11 +30,21 @@ pub enum Error {
     BadEncoding(str::Utf8Error),
     /// unexpected decoder
     UnexpectedDecoder(byteorder::Error),
+    /// value could not be (de)serialized
+    Serialization(String),
+    /// files which must share key/value classes or compression settings do not
+    IncompatibleHeader(String),
+    /// key appended to a sorted file sorts before the previous key, holds the record number
+    KeyOutOfOrder(u64),
+    /// data does not match its checksum, holds the offset of the failing chunk
+    ChecksumMismatch(u64),
+    /// record or block structure is inconsistent (file corrupted)
+    BadRecord(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
-        match &*self {
+        match self {
             Error::BadMagic(ref m) => {
                 write!(
                     f,
@@ -52,6 +62,13 @@ impl fmt::Display for Error {
             Error::UnsupportedCodec(ref codec) => write!(f, "unsupported codec: '{}'", codec),
             Error::BadEncoding(ref e) => write!(f, "utf8 error: {}", e),
             Error::UnexpectedDecoder(ref e) => write!(f, "decoding error: {}", e),
+            Error::Serialization(ref m) => write!(f, "serialization error: {}", m),
+            Error::IncompatibleHeader(ref m) => write!(f, "incompatible header: {}", m),
+            Error::KeyOutOfOrder(ref n) => write!(f, "key out of order at record {}", n),
+            Error::BadRecord(ref m) => write!(f, "corrupt record: {}", m),
+            Error::ChecksumMismatch(ref offset) => {
+                write!(f, "checksum error at offset {}", offset)
+            }
         }
     }
 }
@@ -67,15 +84,27 @@ impl error::Error for Error {
     }
 }
 
+// io::Read wrappers such as the checksum reader report our errors inside an io::Error
+fn is_wrapped(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<Error>())
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
+        if is_wrapped(&err) {
+            let inner = err.into_inner().expect("checked");
+            return *inner.downcast::<Error>().expect("checked");
+        }
         Error::IO(err)
     }
 }
 
 impl From<byteorder::Error> for Error {
     fn from(err: byteorder::Error) -> Error {
-        Error::UnexpectedDecoder(err)
+        match err {
+            byteorder::Error::Io(e) if is_wrapped(&e) => e.into(),
+            _ => Error::UnexpectedDecoder(err),
+        }
     }
 }
 
@@ -0,0 +1,376 @@
+//! Importing CSV and JSON Lines into sequencefiles. A key and a value column are mapped to
+//! Writable classes, cells are parsed after the class: numbers for the integer and floating
+//! point Writables, strings for Text, base64 or hex for BytesWritable and for classes not known
+//! here, which are taken as already serialized.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::import::Importer;
+//! # use sequencefile::{Codec, CompressionType};
+//! # use std::fs::File;
+//! # use std::io::BufReader;
+//! # fn main() -> sequencefile::Result<()> {
+//! // id,name
+//! // 1,alice
+//! Importer::new("id", "LongWritable", "name", "Text")
+//!     .compression(CompressionType::Block, Codec::Snappy)
+//!     .import_csv(BufReader::new(File::open("users.csv")?), File::create("users.seq")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::compress::{Codec, CompressionType};
+use crate::errors::{Error, Result};
+use crate::json::BytesEncoding;
+use crate::value::{self, Value};
+use crate::writer::{Writer, WriterConfig};
+use serde_json::{self, Map, Value as Json};
+use std::io;
+use std::str::FromStr;
+
+/// Writes rows of CSV or JSON Lines input as records
+#[derive(Debug, Clone)]
+pub struct Importer {
+    key_column: String,
+    value_column: String,
+    config: WriterConfig,
+    bytes: BytesEncoding,
+    delimiter: char,
+}
+
+impl Importer {
+    /// Importer of the `key_column` and `value_column` of each row. Classes without a package,
+    /// e.g. `LongWritable`, are taken from `org.apache.hadoop.io`. The column of a
+    /// `NullWritable` is not read and may be empty.
+    pub fn new(
+        key_column: &str,
+        key_class: &str,
+        value_column: &str,
+        value_class: &str,
+    ) -> Importer {
+        Importer {
+            key_column: key_column.to_string(),
+            value_column: value_column.to_string(),
+            config: WriterConfig::new(&qualify(key_class), &qualify(value_class)),
+            bytes: BytesEncoding::Base64,
+            delimiter: ',',
+        }
+    }
+
+    /// Sets the output compression type and codec
+    pub fn compression(mut self, compression_type: CompressionType, codec: Codec) -> Self {
+        self.config = self.config.compression(compression_type, codec);
+        self
+    }
+
+    /// Adds a metadata entry
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.config = self.config.metadata(key, value);
+        self
+    }
+
+    /// Sets the encoding of bytes cells, base64 by default
+    pub fn bytes(mut self, bytes: BytesEncoding) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Sets the CSV field delimiter, `,` by default
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Imports CSV with a header row naming the columns. Fields may be quoted with `"`,
+    /// doubling quotes inside them.
+    ///
+    /// # Failures
+    /// Returns `Error::Serialization` with the line number if a row is malformed or a cell
+    /// doesn't parse as its class
+    pub fn import_csv<R: io::BufRead, W: io::Write>(&self, input: R, output: W) -> Result<W> {
+        if !self.delimiter.is_ascii() {
+            return Err(Error::Serialization(format!(
+                "CSV delimiter '{}' is not ASCII",
+                self.delimiter
+            )));
+        }
+        let mut csv = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .from_reader(input);
+        let header = csv.headers().map_err(csv_error)?.clone();
+        if header.is_empty() {
+            return Err(Error::Serialization("missing CSV header".to_string()));
+        }
+        let key = self.column_index(&self.config.key_class, &self.key_column, &header)?;
+        let value = self.column_index(&self.config.value_class, &self.value_column, &header)?;
+
+        let mut writer = Writer::<W, Vec<u8>, Vec<u8>>::new(output, self.config.clone())?;
+        for row in csv.records() {
+            let row = row.map_err(csv_error)?;
+            let line = row.position().map_or(0, |p| p.line()) as usize;
+            let cell = |index: Option<usize>| match index {
+                Some(i) => match row.get(i) {
+                    Some(cell) => Ok(Cell::Str(cell.to_string())),
+                    None => Err(Error::Serialization(format!(
+                        "expected {} fields",
+                        header.len()
+                    ))),
+                },
+                None => Ok(Cell::Null),
+            };
+            let record = cell(key)
+                .and_then(|k| self.encode(&self.config.key_class, k))
+                .and_then(|k| Ok((k, self.encode(&self.config.value_class, cell(value)?)?)));
+            match record {
+                Ok((k, v)) => writer.append_raw(&k, &v)?,
+                Err(e) => return Err(at_line(line, e)),
+            }
+        }
+        writer.close()
+    }
+
+    /// Imports JSON Lines of objects, blank lines are skipped
+    ///
+    /// # Failures
+    /// Returns `Error::Serialization` with the line number if a line isn't a JSON object,
+    /// lacks a column or a cell doesn't parse as its class
+    pub fn import_json<R: io::BufRead, W: io::Write>(&self, input: R, output: W) -> Result<W> {
+        let mut writer = Writer::<W, Vec<u8>, Vec<u8>>::new(output, self.config.clone())?;
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = parse_json_object(&line).and_then(|mut object| {
+                let key = self.json_cell(&self.config.key_class, &self.key_column, &mut object)?;
+                let value =
+                    self.json_cell(&self.config.value_class, &self.value_column, &mut object)?;
+                Ok((
+                    self.encode(&self.config.key_class, key)?,
+                    self.encode(&self.config.value_class, value)?,
+                ))
+            });
+            match record {
+                Ok((k, v)) => writer.append_raw(&k, &v)?,
+                Err(e) => return Err(at_line(i + 1, e)),
+            }
+        }
+        writer.close()
+    }
+
+    fn column_index(
+        &self,
+        class: &str,
+        column: &str,
+        header: &csv::StringRecord,
+    ) -> Result<Option<usize>> {
+        if class == value::NULL_WRITABLE {
+            return Ok(None);
+        }
+        match header.iter().position(|name| name == column) {
+            Some(i) => Ok(Some(i)),
+            None => Err(Error::Serialization(format!(
+                "no column '{}' in CSV header",
+                column
+            ))),
+        }
+    }
+
+    fn json_cell(&self, class: &str, column: &str, object: &mut Map<String, Json>) -> Result<Cell> {
+        if class == value::NULL_WRITABLE {
+            return Ok(Cell::Null);
+        }
+        let cell = match object.remove(column) {
+            Some(cell) => cell,
+            None => return Err(Error::Serialization(format!("no field '{}'", column))),
+        };
+        match cell {
+            Json::Null => Ok(Cell::Null),
+            Json::Bool(b) => Ok(Cell::Str(b.to_string())),
+            Json::Number(n) => Ok(Cell::Str(n.to_string())),
+            Json::String(s) => Ok(Cell::Str(s)),
+            Json::Array(_) | Json::Object(_) => Err(Error::Serialization(format!(
+                "field '{}' is not a scalar",
+                column
+            ))),
+        }
+    }
+
+    fn encode(&self, class: &str, cell: Cell) -> Result<Vec<u8>> {
+        let text = match cell {
+            Cell::Str(text) => text,
+            Cell::Null if class == value::NULL_WRITABLE => return Value::Null.encode(class),
+            Cell::Null => return Err(Error::Serialization(format!("null for {}", class))),
+        };
+        let value = match class {
+            value::NULL_WRITABLE => Value::Null,
+            value::BOOLEAN_WRITABLE => Value::Boolean(parse(class, &text)?),
+            value::BYTE_WRITABLE => Value::Byte(parse(class, &text)?),
+            value::SHORT_WRITABLE => Value::Short(parse(class, &text)?),
+            value::INT_WRITABLE | value::VINT_WRITABLE => Value::Int(parse(class, &text)?),
+            value::LONG_WRITABLE | value::VLONG_WRITABLE => Value::Long(parse(class, &text)?),
+            value::FLOAT_WRITABLE => Value::Float(parse(class, &text)?),
+            value::DOUBLE_WRITABLE => Value::Double(parse(class, &text)?),
+            value::TEXT => Value::Text(text),
+            value::BYTES_WRITABLE => Value::Bytes(self.bytes.decode(&text)?),
+            _ => Value::Raw(self.bytes.decode(&text)?),
+        };
+        value.encode(class)
+    }
+}
+
+// a cell of either format, before parsing it as its class
+enum Cell {
+    Null,
+    Str(String),
+}
+
+fn qualify(class: &str) -> String {
+    if class.contains('.') {
+        class.to_string()
+    } else {
+        format!("org.apache.hadoop.io.{}", class)
+    }
+}
+
+fn parse<T: FromStr>(class: &str, text: &str) -> Result<T> {
+    text.trim()
+        .parse()
+        .map_err(|_| Error::Serialization(format!("cannot parse '{}' as {}", text, class)))
+}
+
+fn at_line(line: usize, e: Error) -> Error {
+    match e {
+        Error::Serialization(m) => Error::Serialization(format!("line {}: {}", line, m)),
+        e => Error::Serialization(format!("line {}: {}", line, e)),
+    }
+}
+
+fn csv_error(e: csv::Error) -> Error {
+    let message = e.to_string();
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => Error::IO(e),
+        _ => Error::Serialization(message),
+    }
+}
+
+fn parse_json_object(line: &str) -> Result<Map<String, Json>> {
+    match serde_json::from_str(line) {
+        Ok(Json::Object(fields)) => Ok(fields),
+        Ok(_) => Err(Error::Serialization("expected a JSON object".to_string())),
+        Err(e) => Err(Error::Serialization(format!("invalid JSON: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Importer;
+    use crate::json::BytesEncoding;
+    use crate::reader::Reader;
+    use crate::value::Value;
+    use crate::writable::BytesWritable;
+    use crate::{Codec, CompressionType, Error, Text};
+    use std::io::Cursor;
+
+    #[test]
+    fn imports_csv() {
+        let csv = "name,id,score\n\
+                   alice,1,0.5\n\
+                   \n\
+                   \"smith, \"\"bob\"\"\",2,1\n\
+                   \"multi\nline\",3,2\n";
+        let file = Importer::new("id", "LongWritable", "name", "Text")
+            .compression(CompressionType::Block, Codec::Gzip)
+            .import_csv(Cursor::new(csv), Vec::new())
+            .unwrap();
+
+        let reader = Reader::<_, i64, Text>::new(Cursor::new(file)).unwrap();
+        assert_eq!(CompressionType::Block, reader.header.compression_type);
+        assert_eq!("org.apache.hadoop.io.LongWritable", reader.header.key_class);
+        let records: Vec<_> = reader
+            .map(|kv| kv.unwrap())
+            .map(|(k, v)| (k, v.to_string().into_owned()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, "alice".to_string()),
+                (2, "smith, \"bob\"".to_string()),
+                (3, "multi\nline".to_string())
+            ],
+            records
+        );
+    }
+
+    #[test]
+    fn imports_json_lines() {
+        let json = "{\"id\": 7, \"payload\": \"AQID\", \"tags\": [1, {\"a\": null}]}\n\
+                    \n\
+                    {\"payload\":\"\", \"id\":\"-8\"}\n";
+        let file = Importer::new("id", "VIntWritable", "payload", "BytesWritable")
+            .import_json(Cursor::new(json), Vec::new())
+            .unwrap();
+
+        let reader = Reader::<_, Vec<u8>, BytesWritable>::new(Cursor::new(file)).unwrap();
+        let class = reader.header.key_class.clone();
+        let records: Vec<_> = reader
+            .map(|kv| kv.unwrap())
+            .map(|(k, v)| (Value::decode(&class, &k).unwrap(), v.0))
+            .collect();
+        assert_eq!(
+            vec![(Value::Int(7), vec![1, 2, 3]), (Value::Int(-8), vec![])],
+            records
+        );
+
+        let file = Importer::new("", "NullWritable", "v", "com.example.Custom")
+            .bytes(BytesEncoding::Hex)
+            .import_json(Cursor::new("{\"v\": \"cafe\"}"), Vec::new())
+            .unwrap();
+        let mut reader = Reader::<_, Vec<u8>, Vec<u8>>::new(Cursor::new(file)).unwrap();
+        assert_eq!((vec![], vec![0xca, 0xfe]), reader.next().unwrap().unwrap());
+    }
+
+    #[test]
+    fn reports_bad_rows_with_line() {
+        let importer = Importer::new("id", "LongWritable", "name", "Text");
+        let errors = vec![
+            importer
+                .import_csv(Cursor::new("id,name\n1,a\nx,b\n"), Vec::new())
+                .map(|_| ()),
+            importer
+                .import_csv(Cursor::new("id\n1\n"), Vec::new())
+                .map(|_| ()),
+            importer
+                .import_json(
+                    Cursor::new("{\"id\":1,\"name\":\"a\"}\n{\"id\":2,"),
+                    Vec::new(),
+                )
+                .map(|_| ()),
+            importer
+                .import_json(Cursor::new("{\"id\":1}"), Vec::new())
+                .map(|_| ()),
+        ];
+
+        let messages: Vec<_> = errors
+            .into_iter()
+            .map(|e| match e {
+                Err(Error::Serialization(m)) => m,
+                other => panic!("expected serialization error, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            "line 3: cannot parse 'x' as org.apache.hadoop.io.LongWritable",
+            messages[0]
+        );
+        assert_eq!("no column 'name' in CSV header", messages[1]);
+        assert!(messages[2].starts_with("line 2: "), "{}", messages[2]);
+        assert_eq!("line 1: no field 'name'", messages[3]);
+
+        let nested = format!("{{\"id\":{}1{}}}", "[".repeat(100_000), "]".repeat(100_000));
+        for line in &["{\"id\":+1}", "{\"id\":1.}", "{\"id\":.5}", nested.as_str()] {
+            match importer.import_json(Cursor::new(*line), Vec::new()) {
+                Err(Error::Serialization(m)) => assert!(m.contains("column"), "{}", m),
+                other => panic!("expected serialization error, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
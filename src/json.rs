@@ -0,0 +1,212 @@
+//! JSON Lines rendering of records, one `{"key": ..., "value": ...}` object per line. Known
+//! Writables become JSON numbers, strings, booleans or null, other classes and BytesWritable
+//! are encoded as base64 or hex strings.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::json::{to_json, BytesEncoding};
+//! # use sequencefile::Reader;
+//! # use std::fs::File;
+//! # use std::io;
+//! # fn main() -> sequencefile::Result<()> {
+//! // like `seqfile cat --format json part-r-00000 | jq .value`
+//! let reader = Reader::new(File::open("part-r-00000")?)?;
+//! to_json(reader, io::stdout().lock(), BytesEncoding::Base64)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::reader::Reader;
+use crate::value::Value;
+use crate::Header;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{self, Value as Json};
+use std::io;
+
+/// String encoding of opaque bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesEncoding {
+    /// standard base64 with padding
+    #[default]
+    Base64,
+    /// lowercase hex without separators
+    Hex,
+}
+
+impl BytesEncoding {
+    /// Encodes bytes as a string
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BytesEncoding::Base64 => STANDARD.encode(bytes),
+            BytesEncoding::Hex => hex::encode(bytes),
+        }
+    }
+
+    /// Decodes a string produced by `encode`
+    ///
+    /// # Failures
+    /// Returns `Error::Serialization` if the string isn't valid in this encoding
+    pub fn decode(self, encoded: &str) -> Result<Vec<u8>> {
+        let decoded = match self {
+            BytesEncoding::Base64 => STANDARD.decode(encoded).ok(),
+            BytesEncoding::Hex => hex::decode(encoded).ok(),
+        };
+        decoded
+            .ok_or_else(|| Error::Serialization(format!("invalid {:?} bytes: '{}'", self, encoded)))
+    }
+}
+
+/// Renders a value as JSON. Non-finite floats, which JSON can't represent, become strings.
+pub fn value_to_json(value: &Value, bytes: BytesEncoding) -> String {
+    to_json_value(value, bytes).to_string()
+}
+
+fn to_json_value(value: &Value, bytes: BytesEncoding) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Boolean(v) => Json::from(*v),
+        Value::Byte(v) => Json::from(*v),
+        Value::Short(v) => Json::from(*v),
+        Value::Int(v) => Json::from(*v),
+        Value::Long(v) => Json::from(*v),
+        // through the shortest decimal of the f32, so 0.1f32 isn't widened to 0.10000000149011612
+        Value::Float(v) if v.is_finite() => {
+            Json::from(v.to_string().parse().unwrap_or(f64::from(*v)))
+        }
+        Value::Double(v) if v.is_finite() => Json::from(*v),
+        Value::Float(v) => Json::from(v.to_string()),
+        Value::Double(v) => Json::from(v.to_string()),
+        Value::Text(v) => Json::from(v.as_str()),
+        Value::Bytes(v) | Value::Raw(v) => Json::from(bytes.encode(v)),
+    }
+}
+
+/// Writes records of a file as JSON Lines
+#[derive(Debug)]
+pub struct JsonLinesWriter<W: io::Write> {
+    output: W,
+    key_class: String,
+    value_class: String,
+    bytes: BytesEncoding,
+}
+
+impl<W: io::Write> JsonLinesWriter<W> {
+    /// Writer of records typed after the header's key and value classes
+    pub fn new(output: W, header: &Header, bytes: BytesEncoding) -> JsonLinesWriter<W> {
+        JsonLinesWriter {
+            output,
+            key_class: header.key_class.clone(),
+            value_class: header.value_class.clone(),
+            bytes,
+        }
+    }
+
+    /// Writes a serialized key and value as one line
+    ///
+    /// # Failures
+    /// Returns an `Error` if the bytes aren't valid for their class or the output fails
+    pub fn write(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let key = Value::decode(&self.key_class, key)?;
+        let value = Value::decode(&self.value_class, value)?;
+        let record = serde_json::json!({
+            "key": to_json_value(&key, self.bytes),
+            "value": to_json_value(&value, self.bytes),
+        });
+        serde_json::to_writer(&mut self.output, &record).map_err(io::Error::from)?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Returns the underlying io::Write
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+/// Writes every record of `reader` to `output` as JSON Lines, returning the output
+///
+/// # Failures
+/// Returns an `Error` if the file is malformed or the output cannot be written
+pub fn to_json<R: io::Read, W: io::Write>(
+    reader: Reader<R, Vec<u8>, Vec<u8>>,
+    output: W,
+    bytes: BytesEncoding,
+) -> Result<W> {
+    let mut writer = JsonLinesWriter::new(output, &reader.header, bytes);
+    for kv in reader {
+        let (key, value) = kv?;
+        writer.write(&key, &value)?;
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_json, value_to_json, BytesEncoding};
+    use crate::reader::Reader;
+    use crate::value::Value;
+    use crate::writable::BytesWritable;
+    use crate::writer::{Writer, WriterConfig};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn renders_values() {
+        let base64 = BytesEncoding::Base64;
+        assert_eq!("null", value_to_json(&Value::Null, base64));
+        assert_eq!("-3", value_to_json(&Value::Long(-3), base64));
+        assert_eq!("1.5", value_to_json(&Value::Double(1.5), base64));
+        assert_eq!("0.1", value_to_json(&Value::Float(0.1), base64));
+        assert_eq!("\"NaN\"", value_to_json(&Value::Float(f32::NAN), base64));
+        assert_eq!(
+            "\"a\\\"b\\\\c\\n\\u0000\"",
+            value_to_json(&Value::Text("a\"b\\c\n\0".to_string()), base64)
+        );
+
+        for &(bytes, encoded) in &[
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\xfe\x00", "//4A"),
+        ] {
+            assert_eq!(encoded, BytesEncoding::Base64.encode(bytes));
+            assert_eq!(bytes, &BytesEncoding::Base64.decode(encoded).unwrap()[..]);
+        }
+        assert_eq!(vec![0xca, 0xfe], BytesEncoding::Hex.decode("caFE").unwrap());
+        assert!(BytesEncoding::Hex.decode("caf").is_err());
+        assert!(BytesEncoding::Base64.decode("Zm9v!A==").is_err());
+        assert_eq!(
+            "\"ff00\"",
+            value_to_json(&Value::Raw(vec![0xff, 0]), BytesEncoding::Hex)
+        );
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let reader =
+            Reader::new(File::open("test_data/abc_long_text_gzip_record.seq").unwrap()).unwrap();
+        let json =
+            String::from_utf8(to_json(reader, Vec::new(), BytesEncoding::Base64).unwrap()).unwrap();
+        let lines: Vec<_> = json.lines().collect();
+        assert_eq!(26, lines.len());
+        assert_eq!("{\"key\":25,\"value\":\"\\u0000Z\"}", lines[25]);
+
+        let config = WriterConfig::new("org.apache.hadoop.io.BytesWritable", "com.example.Custom");
+        let mut writer = Writer::<_, BytesWritable, Vec<u8>>::new(Vec::new(), config).unwrap();
+        writer
+            .append(&BytesWritable(vec![1, 2, 3]), &vec![0xca, 0xfe])
+            .unwrap();
+        let file = writer.close().unwrap();
+
+        let reader = Reader::new(Cursor::new(file)).unwrap();
+        let json = to_json(reader, Vec::new(), BytesEncoding::Hex).unwrap();
+        assert_eq!(
+            "{\"key\":\"010203\",\"value\":\"cafe\"}\n",
+            String::from_utf8(json).unwrap()
+        );
+    }
+}
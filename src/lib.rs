@@ -1,4 +1,4 @@
-//! Prototype streaming library for reading Hadoop sequencefiles
+//! Prototype streaming library for reading and writing Hadoop sequencefiles
 //!
 //! # Example
 //! ```ignore
@@ -29,9 +29,45 @@
     unused_import_braces
 )]
 
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "datafusion")]
+extern crate async_trait;
+#[cfg(feature = "json")]
+extern crate base64;
 extern crate byteorder;
 extern crate bzip2;
+extern crate crc;
+#[cfg(feature = "import")]
+extern crate csv;
+#[cfg(feature = "datafusion")]
+extern crate datafusion as datafusion_crate;
 extern crate flate2;
+#[cfg(feature = "object_store")]
+extern crate futures;
+#[cfg(feature = "json")]
+extern crate hex;
+#[cfg(feature = "object_store")]
+extern crate object_store as object_store_crate;
+#[cfg(feature = "parquet")]
+extern crate parquet as parquet_crate;
+#[cfg(feature = "webhdfs")]
+extern crate percent_encoding;
+#[cfg(feature = "derive")]
+extern crate sequencefile_derive;
+#[cfg(feature = "serde")]
+extern crate serde as serde_crate;
+#[cfg(any(feature = "json", feature = "webhdfs"))]
+extern crate serde_json;
+extern crate snap;
+#[cfg(test)]
+extern crate tempfile;
+#[cfg(all(test, feature = "datafusion"))]
+extern crate tokio;
+#[cfg(feature = "webhdfs")]
+extern crate ureq;
 
 use std::collections::HashMap;
 
@@ -40,7 +76,7 @@ pub type ByteString = Vec<u8>;
 
 /// Sequencefile header, metadata about the file, e.g. key/value types, version, compression
 /// and some internal state for properly decoding
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Header {
     /// Sequencefile version
     /// Version 4 - block compression
@@ -65,22 +101,80 @@ pub struct Header {
     sync_marker: ByteString,
 }
 
+impl Header {
+    /// 16 byte marker written between blocks and periodically between records
+    pub fn sync_marker(&self) -> &[u8] {
+        &self.sync_marker
+    }
+}
+
 // modules
 mod compress;
+#[cfg(any(feature = "object_store", feature = "webhdfs"))]
+mod ranged;
 mod text;
 mod util;
 
+/// Arrow `RecordBatch` export
+#[cfg(feature = "arrow")]
+pub mod arrow;
+/// Dynamic Bloom filters of BloomMapFiles
+pub mod bloom;
+/// Reading and writing Hadoop `.crc` checksum files
+pub mod checksum;
+/// atomic commit of files and output directories
+pub mod commit;
+/// rewriting files with another compression type or codec
+pub mod convert;
+/// DataFusion `TableProvider` of sequencefiles
+#[cfg(feature = "datafusion")]
+pub mod datafusion;
+/// reading job output directories of part files
+pub mod directory;
 /// Error
 pub mod errors;
+/// importing CSV and JSON Lines into sequencefiles
+#[cfg(feature = "import")]
+pub mod import;
+/// JSON Lines rendering of records
+#[cfg(feature = "json")]
+pub mod json;
+/// MapFile directories of sorted data and index sequencefiles
+pub mod mapfile;
+/// k-way merge of sorted sequencefiles
+pub mod merge;
+/// reading sequencefiles from object stores with ranged GETs
+#[cfg(feature = "object_store")]
+pub mod object_store;
+/// conversion of sequencefiles to Parquet
+#[cfg(feature = "parquet")]
+pub mod parquet;
 pub mod reader;
+/// writing streams of records to files rolled by size, record count or age
+pub mod rolling;
+/// serde (de)serialization using Hadoop DataInput/DataOutput conventions
+#[cfg(feature = "serde")]
+pub mod serde;
+/// external merge sort of sequencefiles
+pub mod sorter;
+/// dynamically typed values of common Writables, decoded by class name
+pub mod value;
+/// fsck-style verification of whole files
+pub mod verify;
+/// reading and writing sequencefiles over WebHDFS
+#[cfg(feature = "webhdfs")]
+pub mod webhdfs;
 /// writable trait and some implementations
 pub mod writable;
+pub mod writer;
 
 // exports
-pub use compress::{Codec, CompressionType};
-pub use errors::*;
-pub use reader::*;
-pub use text::*;
+pub use crate::compress::{Codec, CompressionType};
+pub use crate::errors::*;
+pub use crate::reader::*;
+pub use crate::text::*;
+pub use crate::writer::*;
 
 #[cfg(test)]
+#[allow(missing_docs)]
 pub mod tests;
@@ -0,0 +1,571 @@
+//! Hadoop MapFiles, a directory holding a key-sorted `data` sequencefile and an `index`
+//! sequencefile of (key, LongWritable offset into `data`) entries, and the SetFile,
+//! ArrayFile and BloomMapFile variants built on them
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::mapfile::{MapFileReader, MapFileWriter};
+//! # use sequencefile::{Text, WriterConfig};
+//! # fn main() -> sequencefile::Result<()> {
+//! let config = WriterConfig::new("org.apache.hadoop.io.Text", "org.apache.hadoop.io.LongWritable");
+//! let mut writer = MapFileWriter::<Text, i64>::create("/path/to/mapfile", config)?;
+//! writer.append(&Text::from("some key"), &1)?;
+//! writer.close()?;
+//!
+//! let mut map = MapFileReader::<Text, i64>::open("/path/to/mapfile")?;
+//! let value = map.get(&Text::from("some key"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bloom::DynamicBloomFilter;
+use crate::errors::{Error, Result};
+use crate::reader::Reader;
+use crate::writable::{NullWritable, Writable};
+use crate::writer::{Writer, WriterConfig};
+use crate::{Codec, CompressionType};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+/// Name of the sorted data file within a MapFile directory
+pub const DATA_FILE_NAME: &str = "data";
+
+/// Name of the index file within a MapFile directory
+pub const INDEX_FILE_NAME: &str = "index";
+
+/// Name of the serialized `DynamicBloomFilter` within a BloomMapFile directory
+pub const BLOOM_FILE_NAME: &str = "bloom";
+
+/// Default number of records between index entries, like `io.map.index.interval`
+pub const DEFAULT_INDEX_INTERVAL: u64 = 128;
+
+const INDEX_VALUE_CLASS: &str = "org.apache.hadoop.io.LongWritable";
+
+/// Point and nearest-key lookups on a MapFile. The index is loaded in memory,
+/// lookups binary search it and scan the data file from the closest preceding entry.
+#[derive(Debug)]
+pub struct MapFileReader<K: Writable + Ord, V: Writable> {
+    data: Reader<File, K, V>,
+    index: Vec<(K, u64)>,
+    first_position: u64,
+}
+
+impl<K: Writable + Ord, V: Writable> MapFileReader<K, V> {
+    /// Opens a MapFile directory and loads its index
+    ///
+    /// # Failures
+    /// Returns an `Error` if either file is missing or malformed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<MapFileReader<K, V>> {
+        let dir = dir.as_ref();
+        let data = Reader::new(File::open(dir.join(DATA_FILE_NAME))?)?;
+
+        let mut index = Vec::new();
+        for entry in Reader::<File, K, i64>::new(File::open(dir.join(INDEX_FILE_NAME))?)? {
+            let (key, position) = entry?;
+            index.push((key, position as u64));
+        }
+
+        Ok(MapFileReader {
+            first_position: data.position(),
+            data,
+            index,
+        })
+    }
+
+    /// Header of the data file
+    pub fn header(&self) -> &crate::Header {
+        &self.data.header
+    }
+
+    /// Number of index entries loaded
+    pub fn index_len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns the value stored under `key`, the first one if the key was appended
+    /// more than once
+    pub fn get(&mut self, key: &K) -> Result<Option<V>> {
+        match self.get_closest(key, false)? {
+            Some((found, value)) if found == *key => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the entry at or after `key`, or when `before` is set, the entry at or
+    /// before `key`. `None` if there is no such entry.
+    pub fn get_closest(&mut self, key: &K, before: bool) -> Result<Option<(K, V)>> {
+        self.seek_index(key)?;
+
+        let mut previous = None;
+        for entry in self.data.by_ref() {
+            let (found, value) = entry?;
+            if found >= *key {
+                if before && found != *key {
+                    return Ok(previous);
+                }
+                return Ok(Some((found, value)));
+            }
+            if before {
+                previous = Some((found, value));
+            }
+        }
+
+        Ok(previous)
+    }
+
+    /// Iterates every entry from the start of the data file
+    pub fn entries(&mut self) -> Result<&mut Reader<File, K, V>> {
+        self.data.seek(self.first_position)?;
+        Ok(&mut self.data)
+    }
+
+    // positions the data reader at the last index entry strictly before `key`, so the
+    // first occurrence of a repeated key is never skipped
+    fn seek_index(&mut self, key: &K) -> Result<()> {
+        let entry = self.index.partition_point(|(k, _)| k < key);
+        let position = match entry {
+            0 => self.first_position,
+            i => self.index[i - 1].1,
+        };
+        self.data.seek(position)
+    }
+}
+
+/// Reads a SetFile, a MapFile of keys with `NullWritable` values
+#[derive(Debug)]
+pub struct SetFileReader<K: Writable + Ord> {
+    map: MapFileReader<K, NullWritable>,
+}
+
+impl<K: Writable + Ord> SetFileReader<K> {
+    /// Opens a SetFile directory and loads its index
+    ///
+    /// # Failures
+    /// Returns an `Error` if either file is missing or malformed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<SetFileReader<K>> {
+        Ok(SetFileReader {
+            map: MapFileReader::open(dir)?,
+        })
+    }
+
+    /// Tells if `key` is in the set
+    pub fn contains(&mut self, key: &K) -> Result<bool> {
+        Ok(self.map.get(key)?.is_some())
+    }
+}
+
+impl<K: Writable + Ord> Deref for SetFileReader<K> {
+    type Target = MapFileReader<K, NullWritable>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<K: Writable + Ord> DerefMut for SetFileReader<K> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+/// Reads an ArrayFile, a MapFile keyed by the `LongWritable` position of each value
+#[derive(Debug)]
+pub struct ArrayFileReader<V: Writable> {
+    map: MapFileReader<i64, V>,
+}
+
+impl<V: Writable> ArrayFileReader<V> {
+    /// Opens an ArrayFile directory and loads its index
+    ///
+    /// # Failures
+    /// Returns an `Error` if either file is missing or malformed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<ArrayFileReader<V>> {
+        Ok(ArrayFileReader {
+            map: MapFileReader::open(dir)?,
+        })
+    }
+
+    /// Returns the `n`th value, `None` past the end
+    pub fn get(&mut self, n: i64) -> Result<Option<V>> {
+        self.map.get(&n)
+    }
+}
+
+impl<V: Writable> Deref for ArrayFileReader<V> {
+    type Target = MapFileReader<i64, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<V: Writable> DerefMut for ArrayFileReader<V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+/// Reads a BloomMapFile, a MapFile with a `bloom` file holding a `DynamicBloomFilter` of
+/// the serialized keys. Lookups of keys the filter rules out skip the index and data files.
+#[derive(Debug)]
+pub struct BloomMapFileReader<K: Writable + Ord, V: Writable> {
+    map: MapFileReader<K, V>,
+    bloom: DynamicBloomFilter,
+    key_buf: Vec<u8>,
+}
+
+impl<K: Writable + Ord, V: Writable> BloomMapFileReader<K, V> {
+    /// Opens a BloomMapFile directory and loads its index and Bloom filter
+    ///
+    /// # Failures
+    /// Returns an `Error` if a file is missing or malformed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<BloomMapFileReader<K, V>> {
+        let dir = dir.as_ref();
+        let map = MapFileReader::open(dir)?;
+        let bloom =
+            DynamicBloomFilter::read(&mut BufReader::new(File::open(dir.join(BLOOM_FILE_NAME))?))?;
+
+        Ok(BloomMapFileReader {
+            map,
+            bloom,
+            key_buf: Vec::new(),
+        })
+    }
+
+    /// Tells if `key` may be in the file, `false` means it definitely is not
+    pub fn probably_has_key(&mut self, key: &K) -> Result<bool> {
+        self.key_buf.clear();
+        key.write(&mut self.key_buf)?;
+        Ok(self.bloom.membership_test(&self.key_buf))
+    }
+
+    /// Returns the value stored under `key`, consulting the Bloom filter first
+    pub fn get(&mut self, key: &K) -> Result<Option<V>> {
+        if !self.probably_has_key(key)? {
+            return Ok(None);
+        }
+        self.map.get(key)
+    }
+
+    /// The Bloom filter of the file's keys
+    pub fn bloom_filter(&self) -> &DynamicBloomFilter {
+        &self.bloom
+    }
+}
+
+impl<K: Writable + Ord, V: Writable> Deref for BloomMapFileReader<K, V> {
+    type Target = MapFileReader<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<K: Writable + Ord, V: Writable> DerefMut for BloomMapFileReader<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+/// Writes a MapFile directory laid out like Hadoop's `MapFile.Writer`: keys must be
+/// appended in increasing order, and an index entry is written every `index_interval`
+/// records, only once the data file has moved on to a new position.
+#[derive(Debug)]
+pub struct MapFileWriter<K: Writable + Ord + Clone, V: Writable> {
+    data: Writer<File, K, V>,
+    index: Writer<File, K, i64>,
+    index_interval: u64,
+    size: u64,
+    last_key: Option<K>,
+    last_index_position: Option<u64>,
+    last_index_size: Option<u64>,
+}
+
+impl<K: Writable + Ord + Clone, V: Writable> MapFileWriter<K, V> {
+    /// Creates the MapFile directory and its data and index files. The data file uses
+    /// `config`, the index is block compressed with `LongWritable` values.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the directory or files cannot be created
+    pub fn create<P: AsRef<Path>>(dir: P, config: WriterConfig) -> Result<MapFileWriter<K, V>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let index_config = WriterConfig::new(&config.key_class, INDEX_VALUE_CLASS)
+            .compression(CompressionType::Block, Codec::Default);
+        let data = Writer::new(File::create(dir.join(DATA_FILE_NAME))?, config)?;
+        let index = Writer::new(File::create(dir.join(INDEX_FILE_NAME))?, index_config)?;
+
+        Ok(MapFileWriter {
+            data,
+            index,
+            index_interval: DEFAULT_INDEX_INTERVAL,
+            size: 0,
+            last_key: None,
+            last_index_position: None,
+            last_index_size: None,
+        })
+    }
+
+    /// Sets the number of records between index entries
+    pub fn index_interval(mut self, interval: u64) -> Self {
+        self.index_interval = interval.max(1);
+        self
+    }
+
+    /// Appends a key-value pair
+    ///
+    /// # Failures
+    /// Returns `Error::KeyOutOfOrder` if `key` sorts before the previous key
+    pub fn append(&mut self, key: &K, value: &V) -> Result<()> {
+        if let Some(ref last) = self.last_key {
+            if key < last {
+                return Err(Error::KeyOutOfOrder(self.size));
+            }
+        }
+
+        // in block compressed files the position only moves once a block is written,
+        // so this indexes the first key of each block
+        let position = self.data.position();
+        let due = self
+            .last_index_size
+            .is_none_or(|last| self.size >= last + self.index_interval);
+        if due && self.last_index_position.is_none_or(|last| position > last) {
+            self.index.append(key, &(position as i64))?;
+            self.last_index_position = Some(position);
+            self.last_index_size = Some(self.size);
+        }
+
+        self.data.append(key, value)?;
+        self.last_key = Some(key.clone());
+        self.size += 1;
+        Ok(())
+    }
+
+    /// Number of records appended
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    /// Tells if no records were appended
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Flushes and closes the data and index files
+    pub fn close(self) -> Result<()> {
+        self.data.close()?;
+        self.index.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ArrayFileReader, BloomMapFileReader, MapFileReader, MapFileWriter, SetFileReader,
+        BLOOM_FILE_NAME, DATA_FILE_NAME, INDEX_FILE_NAME,
+    };
+    use crate::bloom::{DynamicBloomFilter, HashType};
+    use crate::errors::Error;
+    use crate::reader::Reader;
+    use crate::tests::{long_config, test_config};
+    use crate::writable::{NullWritable, Writable};
+    use crate::writer::{Writer, WriterConfig};
+    use crate::{Codec, CompressionType};
+    use std::fs::{self, File};
+    use tempfile::{self, TempDir};
+
+    const LONG: &str = "org.apache.hadoop.io.LongWritable";
+
+    // keys 0, 10, 20, ... with every fifth key written twice
+    fn write_mapfile(compression: CompressionType) -> TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let config = long_config().compression(compression, Codec::Default);
+        let mut data =
+            Writer::<_, i64, i64>::new(File::create(dir.join(DATA_FILE_NAME)).unwrap(), config)
+                .unwrap();
+        let mut index = Writer::<_, i64, i64>::new(
+            File::create(dir.join(INDEX_FILE_NAME)).unwrap(),
+            long_config(),
+        )
+        .unwrap();
+
+        for i in 0..1000i64 {
+            let key = i * 10;
+            if i % 16 == 0 {
+                index.append(&key, &(data.position() as i64)).unwrap();
+            }
+            data.append(&key, &i).unwrap();
+            if i % 5 == 0 {
+                data.append(&key, &-i).unwrap();
+            }
+        }
+        data.close().unwrap();
+        index.close().unwrap();
+        tmp
+    }
+
+    #[test]
+    fn gets_exact_keys() {
+        for &compression in &[CompressionType::None, CompressionType::Record] {
+            let dir = write_mapfile(compression);
+            let mut map = MapFileReader::<i64, i64>::open(dir.path()).unwrap();
+
+            assert_eq!(63, map.index_len());
+            assert_eq!(Some(0), map.get(&0).unwrap());
+            assert_eq!(Some(321), map.get(&3210).unwrap());
+            assert_eq!(Some(999), map.get(&9990).unwrap());
+            // first of the repeated entries, even across index entries
+            assert_eq!(Some(160), map.get(&1600).unwrap());
+            assert_eq!(None, map.get(&3215).unwrap());
+            assert_eq!(None, map.get(&-1).unwrap());
+            assert_eq!(None, map.get(&10_000).unwrap());
+        }
+    }
+
+    #[test]
+    fn gets_closest_keys() {
+        let dir = write_mapfile(CompressionType::None);
+        let mut map = MapFileReader::<i64, i64>::open(dir.path()).unwrap();
+
+        assert_eq!(Some((3220, 322)), map.get_closest(&3215, false).unwrap());
+        assert_eq!(Some((3210, 321)), map.get_closest(&3215, true).unwrap());
+        assert_eq!(Some((3210, 321)), map.get_closest(&3210, true).unwrap());
+        assert_eq!(Some((0, 0)), map.get_closest(&-5, false).unwrap());
+        assert_eq!(None, map.get_closest(&-5, true).unwrap());
+        assert_eq!(None, map.get_closest(&9995, false).unwrap());
+        assert_eq!(Some((9990, 999)), map.get_closest(&20_000, true).unwrap());
+        // 1600 is written twice, the later entry is the last one at or before 1605
+        assert_eq!(Some((1600, -160)), map.get_closest(&1605, true).unwrap());
+        assert_eq!(1200, map.entries().unwrap().count());
+    }
+
+    #[test]
+    fn writes_readable_mapfile() {
+        for &compression in &[CompressionType::None, CompressionType::Block] {
+            let tmp = tempfile::tempdir().unwrap();
+            let dir = tmp.path();
+            let config = test_config()
+                .compression(compression, Codec::Gzip)
+                .block_size(1024);
+            let mut writer = MapFileWriter::<i64, crate::Text>::create(dir, config)
+                .unwrap()
+                .index_interval(10);
+            for i in 0..1000i64 {
+                writer
+                    .append(&(i * 2), &crate::Text::from(i.to_string()))
+                    .unwrap();
+            }
+            assert_eq!(1000, writer.len());
+            writer.close().unwrap();
+
+            let index =
+                Reader::<_, i64, i64>::new(File::open(dir.join(INDEX_FILE_NAME)).unwrap()).unwrap();
+            assert_eq!(CompressionType::Block, index.header.compression_type);
+            assert_eq!(LONG, index.header.value_class);
+            let entries = index.count();
+
+            let mut map = MapFileReader::<i64, crate::Text>::open(dir).unwrap();
+            assert_eq!(compression, map.header().compression_type);
+            assert_eq!(entries, map.index_len());
+            assert_eq!("321", map.get(&642).unwrap().unwrap().to_string());
+            assert!(map.get(&643).unwrap().is_none());
+            assert_eq!(1000, map.entries().unwrap().count());
+            match compression {
+                CompressionType::None => assert_eq!(100, entries),
+                // one entry per written block
+                _ => assert!(entries > 1 && entries < 100),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_order_keys() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut writer = MapFileWriter::<i64, i64>::create(dir, long_config()).unwrap();
+
+        writer.append(&1, &1).unwrap();
+        writer.append(&1, &2).unwrap();
+        match writer.append(&0, &3) {
+            Err(Error::KeyOutOfOrder(2)) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn reads_set_and_array_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let config = WriterConfig {
+            value_class: "org.apache.hadoop.io.NullWritable".to_string(),
+            ..long_config()
+        };
+        let mut writer = MapFileWriter::<i64, NullWritable>::create(dir, config).unwrap();
+        for i in 0..500i64 {
+            writer.append(&(i * 3), &NullWritable).unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut set = SetFileReader::<i64>::open(dir).unwrap();
+        assert!(set.contains(&300).unwrap());
+        assert!(!set.contains(&301).unwrap());
+        assert_eq!(
+            Some((303, NullWritable)),
+            set.get_closest(&301, false).unwrap()
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let config = test_config();
+        let mut writer = MapFileWriter::<i64, crate::Text>::create(dir, config).unwrap();
+        for i in 0..500i64 {
+            writer
+                .append(&i, &crate::Text::from(format!("value {}", i)))
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut array = ArrayFileReader::<crate::Text>::open(dir).unwrap();
+        assert_eq!("value 42", array.get(42).unwrap().unwrap().to_string());
+        assert!(array.get(500).unwrap().is_none());
+        assert_eq!(500, array.entries().unwrap().count());
+    }
+
+    #[test]
+    fn reads_bloom_map_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut writer = MapFileWriter::<i64, i64>::create(dir, long_config()).unwrap();
+        let mut bloom = DynamicBloomFilter::new(4096, 5, HashType::Murmur, 256);
+        for i in 0..1000i64 {
+            writer.append(&(i * 2), &i).unwrap();
+            let mut key = Vec::new();
+            (i * 2).write(&mut key).unwrap();
+            bloom.add(&key);
+        }
+        writer.close().unwrap();
+        bloom
+            .write(&mut File::create(dir.join(BLOOM_FILE_NAME)).unwrap())
+            .unwrap();
+
+        let mut map = BloomMapFileReader::<i64, i64>::open(dir).unwrap();
+        assert!(map.probably_has_key(&1000).unwrap());
+        assert_eq!(Some(500), map.get(&1000).unwrap());
+        assert_eq!(None, map.get(&1001).unwrap());
+        let ruled_out = (0..1000i64)
+            .filter(|i| !map.probably_has_key(&(i * 2 + 1)).unwrap())
+            .count();
+        assert!(ruled_out > 900, "only {} keys ruled out", ruled_out);
+        assert_eq!(1000, map.entries().unwrap().count());
+
+        fs::remove_file(dir.join(BLOOM_FILE_NAME)).unwrap();
+        assert!(BloomMapFileReader::<i64, i64>::open(dir).is_err());
+    }
+}
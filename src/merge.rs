@@ -0,0 +1,326 @@
+//! K-way merge of already sorted sequencefiles, e.g. the `part-r-NNNNN` outputs of a job
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::merge::Merger;
+//! # use sequencefile::{Reader, Result, Text};
+//! # use std::fs::File;
+//! # fn main() -> Result<()> {
+//! # let paths = ["part-r-00000", "part-r-00001"];
+//! let readers = paths
+//!     .iter()
+//!     .map(|p| Reader::<File, Text, i64>::new(File::open(p)?))
+//!     .collect::<Result<Vec<_>>>()?;
+//!
+//! // sums the values of keys found in several parts
+//! let merged = Merger::new(readers).combine(|_key, a, b| a + b);
+//! for kv in merged {
+//!     println!("{:?}", kv?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::writable::Writable;
+use crate::writer::Writer;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+type KeyOrdering<'a, K> = Rc<dyn Fn(&K, &K) -> Ordering + 'a>;
+type Combiner<'a, K, V> = Box<dyn FnMut(&K, V, V) -> V + 'a>;
+
+/// Merges sources which are each sorted by key into a single sorted stream.
+/// Records with equal keys are yielded in source order, unless a combiner is set.
+pub struct Merger<'a, I, K, V> {
+    sources: Vec<I>,
+    heap: BinaryHeap<Reverse<Head<'a, K, V>>>,
+    ordering: KeyOrdering<'a, K>,
+    combiner: Option<Combiner<'a, K, V>>,
+    started: bool,
+    // a source's error, reported after the record popped before it
+    pending_error: Option<Error>,
+    is_error: bool,
+}
+
+impl<'a, I, K, V> Merger<'a, I, K, V>
+where
+    I: Iterator<Item = Result<(K, V)>>,
+{
+    /// Merges sources sorted by the key's `Ord`, e.g. a `Vec<Reader<R, K, V>>`
+    pub fn new(sources: Vec<I>) -> Merger<'a, I, K, V>
+    where
+        K: Ord,
+    {
+        Merger::with_ordering(sources, |a: &K, b: &K| a.cmp(b))
+    }
+
+    /// Merges sources sorted by a custom key ordering
+    pub fn with_ordering<O>(sources: Vec<I>, ordering: O) -> Merger<'a, I, K, V>
+    where
+        O: Fn(&K, &K) -> Ordering + 'a,
+    {
+        Merger {
+            heap: BinaryHeap::with_capacity(sources.len()),
+            sources,
+            ordering: Rc::new(ordering),
+            combiner: None,
+            started: false,
+            pending_error: None,
+            is_error: false,
+        }
+    }
+
+    /// Folds the values of equal keys into a single record, like a Hadoop combiner
+    pub fn combine<C>(mut self, combiner: C) -> Self
+    where
+        C: FnMut(&K, V, V) -> V + 'a,
+    {
+        self.combiner = Some(Box::new(combiner));
+        self
+    }
+
+    /// Appends every merged record to `writer`, returning the number of records written
+    pub fn write_to<W>(self, writer: &mut Writer<W, K, V>) -> Result<u64>
+    where
+        W: io::Write,
+        K: Writable,
+        V: Writable,
+    {
+        let mut count = 0;
+        for kv in self {
+            let (key, value) = kv?;
+            writer.append(&key, &value)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn refill(&mut self, source: usize) -> Result<()> {
+        if let Some(kv) = self.sources[source].next() {
+            let (key, value) = kv?;
+            self.heap.push(Reverse(Head {
+                key,
+                value,
+                source,
+                ordering: Rc::clone(&self.ordering),
+            }));
+        }
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> Result<Option<(K, V)>> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        if !self.started {
+            self.started = true;
+            for source in 0..self.sources.len() {
+                self.refill(source)?;
+            }
+        }
+
+        let Head {
+            key,
+            mut value,
+            source,
+            ..
+        } = match self.heap.pop() {
+            Some(Reverse(head)) => head,
+            None => return Ok(None),
+        };
+        let mut error = self.refill(source).err();
+
+        if self.combiner.is_some() {
+            while self.peek_equal(&key) {
+                let Reverse(head) = self.heap.pop().expect("peeked");
+                if error.is_none() {
+                    error = self.refill(head.source).err();
+                }
+                let combiner = self.combiner.as_mut().expect("checked");
+                value = combiner(&key, value, head.value);
+            }
+        }
+
+        self.pending_error = error;
+        Ok(Some((key, value)))
+    }
+
+    fn peek_equal(&self, key: &K) -> bool {
+        self.heap
+            .peek()
+            .is_some_and(|head| (self.ordering)(&head.0.key, key) == Ordering::Equal)
+    }
+}
+
+/// Smallest unconsumed record of a source, ties go to the earlier source to keep equal
+/// keys in source order
+struct Head<'a, K, V> {
+    key: K,
+    value: V,
+    source: usize,
+    ordering: KeyOrdering<'a, K>,
+}
+
+impl<'a, K, V> Ord for Head<'a, K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.ordering)(&self.key, &other.key).then_with(|| self.source.cmp(&other.source))
+    }
+}
+
+impl<'a, K, V> PartialOrd for Head<'a, K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, K, V> PartialEq for Head<'a, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a, K, V> Eq for Head<'a, K, V> {}
+
+impl<'a, I, K, V> Iterator for Merger<'a, I, K, V>
+where
+    I: Iterator<Item = Result<(K, V)>>,
+{
+    type Item = Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Result<(K, V)>> {
+        if self.is_error {
+            return None;
+        }
+
+        match self.next_record() {
+            Ok(val) => val.map(Ok),
+            Err(e) => {
+                self.is_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a, I, K, V> fmt::Debug for Merger<'a, I, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Merger")
+            .field("sources", &self.sources.len())
+            .field("combining", &self.combiner.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Merger;
+    use crate::errors::{Error, Result};
+    use crate::reader::Reader;
+    use crate::tests::text_long_config;
+    use crate::writer::Writer;
+    use crate::Text;
+    use std::io::Cursor;
+
+    fn part(keys: &[&str]) -> Reader<Cursor<Vec<u8>>, Text, i64> {
+        let config = text_long_config();
+        let mut writer = Writer::<_, Text, i64>::new(Vec::new(), config).unwrap();
+        for key in keys {
+            writer.append(&Text::from(*key), &1).unwrap();
+        }
+        Reader::new(Cursor::new(writer.close().unwrap())).unwrap()
+    }
+
+    fn parts() -> Vec<Reader<Cursor<Vec<u8>>, Text, i64>> {
+        vec![
+            part(&["a", "c", "e", "e"]),
+            part(&[]),
+            part(&["b", "c", "f"]),
+            part(&["a", "d"]),
+        ]
+    }
+
+    fn keys<I: Iterator<Item = Result<(Text, i64)>>>(merged: I) -> Vec<(String, i64)> {
+        merged
+            .map(|kv| kv.unwrap())
+            .map(|(k, v)| (k.to_string().to_string(), v))
+            .collect()
+    }
+
+    #[test]
+    fn merges_sorted_parts() {
+        let merged = keys(Merger::new(parts()));
+
+        let expected: Vec<_> = ["a", "a", "b", "c", "c", "d", "e", "e", "f"]
+            .iter()
+            .map(|k| (k.to_string(), 1))
+            .collect();
+        assert_eq!(expected, merged);
+    }
+
+    #[test]
+    fn combines_duplicate_keys() {
+        let merged = keys(Merger::new(parts()).combine(|_, a, b| a + b));
+
+        assert_eq!(
+            vec![
+                ("a".to_string(), 2),
+                ("b".to_string(), 1),
+                ("c".to_string(), 2),
+                ("d".to_string(), 1),
+                ("e".to_string(), 2),
+                ("f".to_string(), 1),
+            ],
+            merged
+        );
+    }
+
+    #[test]
+    fn merges_with_custom_ordering() {
+        let sources = vec![
+            vec![Ok((3, 0)), Ok((1, 0))].into_iter(),
+            vec![Ok((2, 1)), Ok((1, 1))].into_iter(),
+        ];
+
+        let merged: Vec<(i32, i32)> = Merger::with_ordering(sources, |a: &i32, b: &i32| b.cmp(a))
+            .map(|kv| kv.unwrap())
+            .collect();
+
+        assert_eq!(vec![(3, 0), (2, 1), (1, 0), (1, 1)], merged);
+    }
+
+    #[test]
+    fn writes_merged_records() {
+        let config = text_long_config();
+        let mut writer = Writer::<_, Text, i64>::new(Vec::new(), config).unwrap();
+
+        let count = Merger::new(parts()).write_to(&mut writer).unwrap();
+        let reader = Reader::<_, Text, i64>::new(Cursor::new(writer.close().unwrap())).unwrap();
+
+        assert_eq!(9, count);
+        assert_eq!(9, reader.count());
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let sources = vec![vec![Ok((1, 1)), Err(Error::EOF), Ok((2, 2))].into_iter()];
+        let mut merged = Merger::new(sources);
+
+        // the record read before the error isn't lost
+        assert_eq!((1, 1), merged.next().unwrap().unwrap());
+        assert!(merged.next().unwrap().is_err());
+        assert!(merged.next().is_none());
+
+        let sources = vec![
+            vec![Ok((1, 1)), Err(Error::EOF)].into_iter(),
+            vec![Ok((1, 2)), Ok((2, 2))].into_iter(),
+        ];
+        let mut merged = Merger::new(sources).combine(|_, a, b| a + b);
+        assert_eq!((1, 3), merged.next().unwrap().unwrap());
+        assert!(merged.next().unwrap().is_err());
+        assert!(merged.next().is_none());
+    }
+}
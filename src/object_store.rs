@@ -0,0 +1,227 @@
+//! Reading sequencefiles from an `object_store::ObjectStore`, e.g. S3, with ranged GETs.
+//! `ObjectFile` is an `io::Read + io::Seek` over an object: the header is fetched on its own,
+//! a split is fetched with a single GET of its byte range, and reading past a split's end,
+//! up to the next sync marker, continues in `fetch_size` ranges.
+//!
+//! Requests block the calling thread. Stores doing network I/O need a Tokio runtime entered
+//! on that thread, see `tokio::runtime::Runtime::enter`, and must not be read from async code.
+//!
+//! # Example
+//! ```no_run
+//! # use object_store::local::LocalFileSystem;
+//! # use object_store::path::Path;
+//! # use object_store::ObjectStore;
+//! # use sequencefile::object_store::{open_split, splits};
+//! # use sequencefile::Text;
+//! # use std::sync::Arc;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let runtime = tokio::runtime::Runtime::new()?;
+//! let store: Arc<dyn ObjectStore> = Arc::new(LocalFileSystem::new_with_prefix("/data")?);
+//! let location = Path::from("events/part-r-00000");
+//! let _guard = runtime.enter();
+//! for range in splits(&store, &location, 64 * 1024 * 1024)? {
+//!     let reader = open_split::<i64, Text>(store.clone(), location.clone(), range)?;
+//!     // records of one split
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::Result;
+use crate::object_store_crate::path::Path;
+use crate::object_store_crate::{ObjectStore, ObjectStoreExt};
+use crate::ranged::{split_ranges, Fetch, RangedFile};
+use crate::reader::Reader;
+use crate::writable::Writable;
+use futures::executor::block_on;
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+
+pub use crate::ranged::{DEFAULT_FETCH_SIZE, HEADER_FETCH_SIZE};
+
+/// Seekable reader of an object, buffering the last fetched range
+#[derive(Debug)]
+pub struct ObjectFile {
+    inner: RangedFile<Object>,
+}
+
+impl ObjectFile {
+    /// Reader of the object at `location`, its size is taken from a HEAD request
+    ///
+    /// # Failures
+    /// Returns `Error::IO`, of kind `io::ErrorKind::NotFound` for a missing object, if the
+    /// request fails
+    pub fn open(store: Arc<dyn ObjectStore>, location: Path) -> Result<ObjectFile> {
+        let len = block_on(store.head(&location))
+            .map_err(io::Error::from)?
+            .size;
+        Ok(ObjectFile::with_len(store, location, len))
+    }
+
+    /// Reader of the object at `location` of a known size, e.g. from a listing
+    pub fn with_len(store: Arc<dyn ObjectStore>, location: Path, len: u64) -> ObjectFile {
+        ObjectFile {
+            inner: RangedFile::new(Object { store, location }, len),
+        }
+    }
+
+    /// Sets the number of bytes fetched per GET outside of a split
+    pub fn fetch_size(mut self, fetch_size: u64) -> Self {
+        self.inner.fetch_size = fetch_size.max(1);
+        self
+    }
+
+    /// Fetches `split` with a single GET once it is reached, and only the header before it
+    pub fn split(mut self, split: Range<u64>) -> Self {
+        self.inner.split = Some(split);
+        self
+    }
+
+    /// Size of the object
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    /// Tells if the object is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl io::Read for ObjectFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl io::Seek for ObjectFile {
+    fn seek(&mut self, position: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+#[derive(Debug)]
+struct Object {
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+}
+
+impl Fetch for Object {
+    fn fetch(&mut self, range: Range<u64>) -> io::Result<Vec<u8>> {
+        let bytes = block_on(self.store.get_range(&self.location, range))?;
+        Ok(bytes.into())
+    }
+}
+
+/// Byte ranges of `split_size` covering the object at `location`, to read with `open_split`
+///
+/// # Failures
+/// Returns `Error::IO` if the HEAD request fails
+pub fn splits(
+    store: &Arc<dyn ObjectStore>,
+    location: &Path,
+    split_size: u64,
+) -> Result<Vec<Range<u64>>> {
+    let len = block_on(store.head(location))
+        .map_err(io::Error::from)?
+        .size;
+    Ok(split_ranges(len, split_size))
+}
+
+/// Reader of the whole object at `location`
+///
+/// # Failures
+/// Returns an `Error` if a request fails or the header is malformed
+pub fn open<K: Writable, V: Writable>(
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+) -> Result<Reader<ObjectFile, K, V>> {
+    Reader::new(ObjectFile::open(store, location)?)
+}
+
+/// Reader of the records of one split of the object at `location`, see `Reader::split`
+///
+/// # Failures
+/// Returns an `Error` if a request fails or the header is malformed
+pub fn open_split<K: Writable, V: Writable>(
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+    split: Range<u64>,
+) -> Result<Reader<ObjectFile, K, V>> {
+    let file = ObjectFile::open(store, location)?.split(split.clone());
+    let mut reader = Reader::new(file)?;
+    reader.split(split.start, split.end)?;
+    Ok(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, open_split, splits};
+    use crate::object_store_crate::local::LocalFileSystem;
+    use crate::object_store_crate::memory::InMemory;
+    use crate::object_store_crate::path::Path;
+    use crate::object_store_crate::{ObjectStore, ObjectStoreExt, PutPayload};
+    use crate::tests::test_config;
+    use crate::writer::Writer;
+    use crate::{Codec, CompressionType, Error, Text};
+    use futures::executor::block_on;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::sync::Arc;
+
+    fn sequencefile() -> Vec<u8> {
+        let config = test_config()
+            .compression(CompressionType::Block, Codec::Gzip)
+            .block_size(2048)
+            .sync_interval(512);
+        let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+        for i in 0..3000 {
+            writer
+                .append(&i, &Text::from(format!("value-{}", i)))
+                .unwrap();
+        }
+        writer.close().unwrap()
+    }
+
+    fn read_splits(store: Arc<dyn ObjectStore>, location: &Path) -> Vec<i64> {
+        let mut keys = Vec::new();
+        let ranges = splits(&store, location, 4096).unwrap();
+        assert!(ranges.len() > 2);
+        for range in ranges {
+            let reader = open_split::<i64, Text>(store.clone(), location.clone(), range).unwrap();
+            keys.extend(reader.map(|kv| kv.unwrap().0));
+        }
+        keys
+    }
+
+    #[test]
+    fn reads_splits_from_memory() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let location = Path::from("job/part-r-00000");
+        block_on(store.put(&location, PutPayload::from(sequencefile()))).unwrap();
+
+        assert_eq!(
+            (0..3000).collect::<Vec<_>>(),
+            read_splits(store.clone(), &location)
+        );
+        let reader = open::<i64, Text>(store.clone(), location).unwrap();
+        assert_eq!(3000, reader.count());
+
+        match open::<i64, Text>(store, Path::from("job/missing")) {
+            Err(Error::IO(e)) => assert_eq!(ErrorKind::NotFound, e.kind()),
+            other => panic!("expected a missing object, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reads_splits_from_local_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("part-r-00000"), sequencefile()).unwrap();
+
+        let store: Arc<dyn ObjectStore> =
+            Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+        let keys = read_splits(store, &Path::from("part-r-00000"));
+        assert_eq!((0..3000).collect::<Vec<_>>(), keys);
+    }
+}
@@ -0,0 +1,255 @@
+//! Converting sequencefiles to Parquet. The schema follows the header's key and value classes,
+//! see `arrow::data_type`, and the classes are kept in the Parquet key/value metadata.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::parquet::{Compression, ParquetConverter, ZstdLevel};
+//! # use std::fs::File;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! // all part files of a job output into one Parquet file
+//! ParquetConverter::new()
+//!     .compression(Compression::ZSTD(ZstdLevel::try_new(3)?))
+//!     .row_group_size(500_000)
+//!     .convert_dir("/data/job-output", File::create("job-output.parquet")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::arrow::{BatchReader, DEFAULT_BATCH_SIZE};
+use crate::errors::{Error, Result};
+use crate::parquet_crate::arrow::ArrowWriter;
+use crate::parquet_crate::errors::ParquetError;
+use crate::parquet_crate::file::metadata::KeyValue;
+use crate::parquet_crate::file::properties::{WriterProperties, DEFAULT_MAX_ROW_GROUP_ROW_COUNT};
+use crate::reader::Reader;
+use crate::util::part_files;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+pub use crate::parquet_crate::basic::{Compression, GzipLevel, ZstdLevel};
+
+/// Parquet metadata key holding the sequencefile key class
+pub const KEY_CLASS_METADATA: &str = "sequencefile.key_class";
+/// Parquet metadata key holding the sequencefile value class
+pub const VALUE_CLASS_METADATA: &str = "sequencefile.value_class";
+
+/// Writes the records of sequencefiles into a Parquet file with `key` and `value` columns
+#[derive(Debug, Clone, Copy)]
+pub struct ParquetConverter {
+    row_group_size: usize,
+    compression: Compression,
+    batch_size: usize,
+}
+
+impl Default for ParquetConverter {
+    fn default() -> Self {
+        ParquetConverter::new()
+    }
+}
+
+impl ParquetConverter {
+    /// Snappy compressed output with row groups of up to 1Mi records
+    pub fn new() -> ParquetConverter {
+        ParquetConverter {
+            row_group_size: DEFAULT_MAX_ROW_GROUP_ROW_COUNT,
+            compression: Compression::SNAPPY,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Sets the maximum number of records per row group
+    pub fn row_group_size(mut self, row_group_size: usize) -> Self {
+        self.row_group_size = row_group_size.max(1);
+        self
+    }
+
+    /// Sets the Parquet compression of column chunks
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the number of records decoded at a time
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Converts `input` into `output`, returning the underlying io::Write
+    ///
+    /// # Failures
+    /// Returns an `Error` if the input is malformed or the output cannot be written
+    pub fn convert<R: io::Read, W: io::Write + Send>(&self, input: R, output: W) -> Result<W> {
+        let batches = self.batches(Reader::new(input)?);
+        let mut writer = self.writer(output, &batches)?;
+        write_batches(&mut writer, batches)?;
+        writer.into_inner().map_err(parquet_error)
+    }
+
+    /// Converts the part files of a directory, in name order, into a single Parquet `output`.
+    /// Hidden files and files starting with `_`, e.g. `_SUCCESS`, are skipped.
+    ///
+    /// # Failures
+    /// Returns `Error::IncompatibleHeader` if the directory has no part files or they have
+    /// different key or value classes
+    pub fn convert_dir<P: AsRef<Path>, W: io::Write + Send>(&self, dir: P, output: W) -> Result<W> {
+        let paths = part_files(dir.as_ref())?;
+        let first = match paths.first() {
+            Some(path) => open(path)?,
+            None => {
+                return Err(Error::IncompatibleHeader(format!(
+                    "no part files in {}",
+                    dir.as_ref().display()
+                )))
+            }
+        };
+
+        let header = first.header.clone();
+        let batches = self.batches(first);
+        let mut writer = self.writer(output, &batches)?;
+        write_batches(&mut writer, batches)?;
+        for path in &paths[1..] {
+            let reader = open(path)?;
+            if reader.header.key_class != header.key_class
+                || reader.header.value_class != header.value_class
+            {
+                return Err(Error::IncompatibleHeader(format!(
+                    "expected {}/{}, found {}/{} in {}",
+                    header.key_class,
+                    header.value_class,
+                    reader.header.key_class,
+                    reader.header.value_class,
+                    path.display()
+                )));
+            }
+            write_batches(&mut writer, self.batches(reader))?;
+        }
+        writer.into_inner().map_err(parquet_error)
+    }
+
+    fn batches<R: io::Read>(&self, reader: Reader<R, Vec<u8>, Vec<u8>>) -> BatchReader<R> {
+        BatchReader::new(reader).batch_size(self.batch_size)
+    }
+
+    fn writer<R: io::Read, W: io::Write + Send>(
+        &self,
+        output: W,
+        batches: &BatchReader<R>,
+    ) -> Result<ArrowWriter<W>> {
+        let header = batches.header();
+        let props = WriterProperties::builder()
+            .set_compression(self.compression)
+            .set_max_row_group_row_count(Some(self.row_group_size))
+            .set_key_value_metadata(Some(vec![
+                KeyValue::new(KEY_CLASS_METADATA.to_string(), header.key_class.clone()),
+                KeyValue::new(VALUE_CLASS_METADATA.to_string(), header.value_class.clone()),
+            ]))
+            .build();
+        ArrowWriter::try_new(output, batches.schema(), Some(props)).map_err(parquet_error)
+    }
+}
+
+fn write_batches<R: io::Read, W: io::Write + Send>(
+    writer: &mut ArrowWriter<W>,
+    batches: BatchReader<R>,
+) -> Result<()> {
+    for batch in batches {
+        writer.write(&batch?).map_err(parquet_error)?;
+    }
+    Ok(())
+}
+
+fn open(path: &Path) -> Result<Reader<File, Vec<u8>, Vec<u8>>> {
+    Reader::new(File::open(path)?)
+}
+
+fn parquet_error(e: ParquetError) -> Error {
+    Error::Serialization(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, ParquetConverter, KEY_CLASS_METADATA};
+    use crate::parquet_crate::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use crate::Error;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use std::fs::{self, File};
+    use std::path::Path;
+    use tempfile;
+
+    fn read_back(path: &Path) -> (usize, Vec<(i64, String)>) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let row_groups = builder.metadata().num_row_groups();
+        let mut rows = Vec::new();
+        for batch in builder.build().unwrap() {
+            let batch = batch.unwrap();
+            let keys = batch.column(0).as_primitive::<Int64Type>();
+            let values = batch.column(1).as_string::<i32>();
+            for i in 0..batch.num_rows() {
+                rows.push((keys.value(i), values.value(i).to_string()));
+            }
+        }
+        (row_groups, rows)
+    }
+
+    #[test]
+    fn converts_file_into_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.parquet");
+        ParquetConverter::new()
+            .row_group_size(10)
+            .compression(Compression::GZIP(Default::default()))
+            .convert(
+                File::open("test_data/abc_long_text_snappy_record.seq").unwrap(),
+                File::create(&path).unwrap(),
+            )
+            .unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let metadata = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap();
+        assert!(metadata.iter().any(|kv| kv.key == KEY_CLASS_METADATA
+            && kv.value.as_deref() == Some("org.apache.hadoop.io.LongWritable")));
+        let column = builder.metadata().row_group(0).column(1);
+        assert_eq!(Compression::GZIP(Default::default()), column.compression());
+
+        let (row_groups, rows) = read_back(&path);
+        assert_eq!(3, row_groups);
+        assert_eq!(26, rows.len());
+        assert_eq!((25, "\0Z".to_string()), rows[25]);
+    }
+
+    #[test]
+    fn converts_part_files_of_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("job-output");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy("test_data/abc_long_text_none.seq", dir.join("part-00001")).unwrap();
+        fs::copy(
+            "test_data/abc_long_text_bzip2_block.seq",
+            dir.join("part-00000"),
+        )
+        .unwrap();
+        fs::copy("test_data/complex.seq", dir.join(".part-00002")).unwrap();
+        File::create(dir.join("_SUCCESS")).unwrap();
+
+        let output = dir.with_extension("parquet");
+        ParquetConverter::new()
+            .convert_dir(&dir, File::create(&output).unwrap())
+            .unwrap();
+        let (_, rows) = read_back(&output);
+        assert_eq!(52, rows.len());
+        assert_eq!((0, "\0A".to_string()), rows[26]);
+
+        fs::copy("test_data/complex.seq", dir.join("part-00002")).unwrap();
+        match ParquetConverter::new().convert_dir(&dir, Vec::new()) {
+            Err(Error::IncompatibleHeader(_)) => (),
+            other => panic!("expected incompatible header, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Seekable reads of remote files served by ranged requests. The header is fetched on its
+//! own, a split with a single request of its byte range, and reads past a split's end, up to
+//! the next sync marker, continue in `fetch_size` ranges.
+
+use std::cmp;
+use std::io;
+use std::ops::Range;
+
+/// Default number of bytes fetched per request outside of a split
+pub const DEFAULT_FETCH_SIZE: u64 = 1024 * 1024;
+
+/// Number of bytes fetched for the header when reading a split
+pub const HEADER_FETCH_SIZE: u64 = 64 * 1024;
+
+/// Source of byte ranges of a file
+pub trait Fetch {
+    /// Fetches the bytes of `range`, which lies within the file
+    fn fetch(&mut self, range: Range<u64>) -> io::Result<Vec<u8>>;
+}
+
+/// `io::Read + io::Seek` over a `Fetch`, buffering the last fetched range
+#[derive(Debug)]
+pub struct RangedFile<F> {
+    source: F,
+    len: u64,
+    position: u64,
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    pub fetch_size: u64,
+    pub split: Option<Range<u64>>,
+}
+
+impl<F: Fetch> RangedFile<F> {
+    pub fn new(source: F, len: u64) -> RangedFile<F> {
+        RangedFile {
+            source,
+            len,
+            position: 0,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            fetch_size: DEFAULT_FETCH_SIZE,
+            split: None,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    // range of the request serving a read at `position`
+    fn fetch_range(&self, position: u64) -> Range<u64> {
+        let end = match self.split {
+            Some(ref split) if position < split.start => {
+                cmp::min(position + HEADER_FETCH_SIZE, split.start)
+            }
+            Some(ref split) if position < split.end => split.end,
+            _ => position + self.fetch_size,
+        };
+        position..cmp::min(end, self.len)
+    }
+}
+
+impl<F: Fetch> io::Read for RangedFile<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len {
+            return Ok(0);
+        }
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position < self.buffer_start || self.position >= buffer_end {
+            let range = self.fetch_range(self.position);
+            let start = range.start;
+            self.buffer = self.source.fetch(range)?;
+            self.buffer_start = start;
+            if self.buffer.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("no bytes at offset {} of {}", start, self.len),
+                ));
+            }
+        }
+
+        let offset = (self.position - self.buffer_start) as usize;
+        let read = cmp::min(buf.len(), self.buffer.len() - offset);
+        buf[..read].copy_from_slice(&self.buffer[offset..offset + read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<F> io::Seek for RangedFile<F> {
+    fn seek(&mut self, position: io::SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            io::SeekFrom::Start(p) => Some(p),
+            io::SeekFrom::End(d) => self.len.checked_add_signed(d),
+            io::SeekFrom::Current(d) => self.position.checked_add_signed(d),
+        };
+        match position {
+            Some(p) => {
+                self.position = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the file",
+            )),
+        }
+    }
+}
+
+/// Byte ranges of `split_size` covering a file of `len` bytes
+pub fn split_ranges(len: u64, split_size: u64) -> Vec<Range<u64>> {
+    let split_size = split_size.max(1);
+    let count = len.div_ceil(split_size).max(1);
+    (0..count)
+        .map(|i| i * split_size..cmp::min((i + 1) * split_size, len))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_ranges, Fetch, RangedFile, HEADER_FETCH_SIZE};
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::ops::Range;
+
+    // serves ranges of a counting pattern, remembering them
+    #[derive(Debug, Default)]
+    struct Requests(Vec<Range<u64>>);
+
+    impl Fetch for Requests {
+        fn fetch(&mut self, range: Range<u64>) -> io::Result<Vec<u8>> {
+            self.0.push(range.clone());
+            Ok(range.map(|i| i as u8).collect())
+        }
+    }
+
+    #[test]
+    fn fetches_header_then_split() {
+        let mut file = RangedFile::new(Requests::default(), 1 << 30);
+        file.fetch_size = 1000;
+        file.split = Some(1 << 20..2 << 20);
+        assert_eq!(0..HEADER_FETCH_SIZE, file.fetch_range(0));
+        assert_eq!(1 << 20..2 << 20, file.fetch_range(1 << 20));
+        assert_eq!(2 << 20..(2 << 20) + 1000, file.fetch_range(2 << 20));
+
+        let mut file = RangedFile::new(Requests::default(), 100);
+        file.fetch_size = 30;
+        file.split = Some(0..50);
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).unwrap();
+        assert_eq!((0..100).collect::<Vec<u8>>(), buf);
+        file.seek(SeekFrom::Start(60)).unwrap();
+        let mut byte = [0];
+        file.read_exact(&mut byte).unwrap();
+        assert_eq!(60, byte[0]);
+        assert_eq!(vec![0..50, 50..80, 80..100, 60..90], file.source.0);
+    }
+
+    #[test]
+    fn covers_files_with_splits() {
+        assert_eq!(vec![0..0], split_ranges(0, 10));
+        assert_eq!(vec![0..10, 10..20, 20..25], split_ranges(25, 10));
+    }
+}
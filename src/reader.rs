@@ -1,9 +1,10 @@
 //! Implementation and structs for a sequencefile reader
 
+use crate::compress;
+use crate::compress::CompressionType;
+use crate::errors::{Error, Result};
+use crate::util::ZeroCompress;
 use byteorder::{BigEndian, ReadBytesExt};
-use compress;
-use compress::CompressionType;
-use errors::{Error, Result};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io;
@@ -11,13 +12,12 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::Cursor;
 use std::str;
-use util::ZeroCompress;
 
 use crate::writable::Writable;
-use {ByteString, Header};
+use crate::{ByteString, Header};
 
-const MAGIC: &str = "SEQ";
-const SYNC_SIZE: usize = 16;
+pub(crate) const MAGIC: &str = "SEQ";
+pub(crate) const SYNC_SIZE: usize = 16;
 
 /// Provides a streaming interface fronted by an Iterator
 /// Only buffers when `CompressionType::Block` is used.
@@ -25,9 +25,12 @@ const SYNC_SIZE: usize = 16;
 pub struct Reader<R: io::Read, K: Writable, V: Writable> {
     /// Sequencefile header
     pub header: Header,
-    reader: BufReader<R>,
+    reader: Input<R>,
     block_buffer: Vec<(K, V)>,
     is_error: bool,
+    header_len: u64,
+    sync_seen: bool,
+    split_end: Option<u64>,
 }
 
 impl<R: io::Read, K: Writable, V: Writable> Reader<R, K, V> {
@@ -37,20 +40,123 @@ impl<R: io::Read, K: Writable, V: Writable> Reader<R, K, V> {
     /// Returns an `Error` if sequencefile header is malformed, e.g. unsupported version or
     /// invalid compression algorithm
     pub fn new(r: R) -> Result<Reader<R, K, V>> {
-        let mut br = BufReader::new(r);
+        let mut input = Input {
+            inner: BufReader::new(r),
+            position: 0,
+        };
 
-        let header = read_header(&mut br)?;
+        let header = read_header(&mut input)?;
+        let header_len = input.position;
 
         Ok(Reader {
             header,
-            reader: br,
+            reader: input,
             block_buffer: Vec::new(),
             is_error: false,
+            header_len,
+            sync_seen: false,
+            split_end: None,
         })
     }
+
+    /// Byte offset of the next record, or of the next block with `CompressionType::Block`
+    /// once the current block is exhausted
+    pub fn position(&self) -> u64 {
+        self.reader.position
+    }
+
+    /// Tells if a sync marker preceded the last record, i.e. it was the first of a block or
+    /// followed one of the periodic markers of uncompressed and record compressed files
+    pub fn sync_seen(&self) -> bool {
+        self.sync_seen
+    }
+}
+
+impl<R: io::Read + io::Seek, K: Writable, V: Writable> Reader<R, K, V> {
+    /// Positions the reader at a byte offset previously returned by `position`, e.g.
+    /// a MapFile index entry. Discards any buffered block and clears a previous error.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the underlying reader cannot seek
+    pub fn seek(&mut self, position: u64) -> Result<()> {
+        self.reader.inner.seek(io::SeekFrom::Start(position))?;
+        self.reader.position = position;
+        self.block_buffer.clear();
+        self.is_error = false;
+        self.split_end = None;
+        Ok(())
+    }
+
+    /// Positions the reader at the first sync marker at or after `position`, or at the first
+    /// record if `position` falls within the header, like Hadoop's `SequenceFile.Reader.sync`.
+    /// Returns false, positioned at the end of the file, if no marker follows `position`.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the underlying reader cannot seek or read
+    pub fn sync(&mut self, position: u64) -> Result<bool> {
+        if position <= self.header_len {
+            let header_len = self.header_len;
+            self.seek(header_len)?;
+            return Ok(true);
+        }
+        self.seek(position)?;
+
+        // sync escape followed by the marker
+        let mut pattern = vec![0xff; 4];
+        pattern.extend_from_slice(&self.header.sync_marker);
+
+        let mut offset = position;
+        let mut window = Vec::new();
+        let mut chunk = [0; 8192];
+        loop {
+            let read = self.reader.inner.read(&mut chunk)?;
+            if read == 0 {
+                self.reader.position = offset + window.len() as u64;
+                return Ok(false);
+            }
+            window.extend_from_slice(&chunk[..read]);
+            if let Some(i) = window
+                .windows(pattern.len())
+                .position(|w| w == &pattern[..])
+            {
+                self.seek(offset + i as u64)?;
+                return Ok(true);
+            }
+            let consumed = window.len().saturating_sub(pattern.len() - 1);
+            window.drain(..consumed);
+            offset += consumed as u64;
+        }
+    }
+
+    /// Restricts the reader to the split `[start, end)` of the file: positions it with
+    /// `sync(start)` and ends iteration at the first sync marker at or after `end`, like
+    /// Hadoop's `SequenceFileRecordReader`. Consecutive splits read every record exactly once.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the underlying reader cannot seek or read
+    pub fn split(&mut self, start: u64, end: u64) -> Result<()> {
+        self.sync(start)?;
+        self.split_end = Some(end);
+        Ok(())
+    }
 }
 
-fn read_header<R: io::Read>(reader: &mut R) -> Result<Header> {
+/// Tracks the byte offset consumed from a buffered reader
+#[derive(Debug)]
+struct Input<R: io::Read> {
+    inner: BufReader<R>,
+    position: u64,
+}
+
+impl<R: io::Read> io::Read for Input<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+pub(crate) fn read_header<R: io::Read>(reader: &mut R) -> Result<Header> {
     let mut magic = [0; 3];
     reader.read_exact(&mut magic)?;
     if magic != MAGIC.as_bytes() {
@@ -131,7 +237,13 @@ impl<R: io::Read, K: Writable, V: Writable> Iterator for Reader<R, K, V> {
             return None;
         }
 
+        let position = self.reader.position;
         match next_element(self) {
+            // the record belongs to the next split
+            Ok(_) if self.sync_seen && self.split_end.is_some_and(|end| position >= end) => {
+                self.is_error = true;
+                None
+            }
             Ok(val) => Some(Ok(val)),
             Err(Error::UnexpectedDecoder(_)) | Err(Error::EOF) => {
                 self.is_error = true;
@@ -148,18 +260,20 @@ impl<R: io::Read, K: Writable, V: Writable> Iterator for Reader<R, K, V> {
 fn next_element<R: io::Read, K: Writable, V: Writable>(
     reader: &mut Reader<R, K, V>,
 ) -> Result<(K, V)> {
+    reader.sync_seen = false;
     if reader.block_buffer.is_empty() || reader.header.compression_type != CompressionType::Block {
         let mut last_sync_marker = [0; SYNC_SIZE];
         let mut kv_length = reader.reader.read_i32::<BigEndian>()? as i64;
 
         // handle sync marker
         if kv_length == -1 {
+            reader.sync_seen = true;
             reader.reader.read_exact(&mut last_sync_marker)?;
             if last_sync_marker.to_vec() != reader.header.sync_marker {
                 return Err(Error::SyncMarkerMismatch);
             }
 
-            if reader.header.compression_type == CompressionType::None {
+            if reader.header.compression_type != CompressionType::Block {
                 kv_length = reader.reader.read_i32::<BigEndian>()? as i64;
             }
         }
@@ -172,8 +286,15 @@ fn next_element<R: io::Read, K: Writable, V: Writable>(
     if reader.header.compression_type == CompressionType::Block {
         let codec = &reader.header.compression_codec.unwrap();
         if reader.block_buffer.is_empty() {
-            // count of kvs in block
-            let kv_count = reader.reader.decode_vint64()? as usize;
+            // count of kvs in block, a file may end with a sync marker instead
+            let kv_count = match reader.reader.decode_vint64() {
+                Err(Error::IO(ref e))
+                    if reader.sync_seen && e.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    return Err(Error::EOF)
+                }
+                kv_count => kv_count? as usize,
+            };
 
             // key lengths
             let kl_buffer = read_buf(&mut reader.reader)?;
@@ -328,7 +449,7 @@ pub fn read_vint(reader: &mut impl io::Read) -> Result<i32> {
 }
 
 fn is_negative_vint(value: i8) -> bool {
-    value < -120 || (value >= -112 && value < 0)
+    value < -120 || (-112..0).contains(&value)
 }
 
 fn decode_vint_size(value: i8) -> i32 {
@@ -0,0 +1,391 @@
+//! Writing a continuous stream of records to a sequence of files, e.g. for an ingester.
+//! A file is rolled, i.e. closed with a final sync marker and replaced by the next
+//! sequentially named one, once it reaches a size, a number of records or an age.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::rolling::RollingWriter;
+//! # use sequencefile::{Text, WriterConfig};
+//! # use std::time::Duration;
+//! # fn main() -> sequencefile::Result<()> {
+//! # let config = WriterConfig::new("org.apache.hadoop.io.LongWritable", "org.apache.hadoop.io.Text");
+//! # let events: Vec<(i64, Text)> = Vec::new();
+//! let mut writer = RollingWriter::<i64, Text>::new("/data/ingest", config)
+//!     .prefix("events")
+//!     .max_bytes(128 * 1024 * 1024)
+//!     .max_age(Duration::from_secs(600))
+//!     .on_roll(|path| println!("completed {}", path.display()));
+//! for (key, value) in events {
+//!     writer.append(&key, &value)?;
+//! }
+//! writer.close()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::commit::{AtomicFile, TempLocation};
+use crate::errors::Result;
+use crate::writable::Writable;
+use crate::writer::{Writer, WriterConfig};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+type RollCallback<'a> = Box<dyn FnMut(&Path) + 'a>;
+
+/// Writes records to `<prefix>-00000`, `<prefix>-00001`, ... in a directory, starting the
+/// next file when the current one reaches one of its limits
+pub struct RollingWriter<'a, K: Writable, V: Writable> {
+    dir: PathBuf,
+    prefix: String,
+    config: WriterConfig,
+    max_bytes: Option<u64>,
+    max_records: Option<u64>,
+    max_age: Option<Duration>,
+    on_roll: Option<RollCallback<'a>>,
+    temp_location: Option<TempLocation>,
+    current: Option<Current<K, V>>,
+    next_index: u64,
+}
+
+struct Current<K: Writable, V: Writable> {
+    path: PathBuf,
+    writer: Writer<Sink, K, V>,
+    records: u64,
+    opened: Instant,
+}
+
+// the file being written, under its final or a temporary name
+#[derive(Debug)]
+enum Sink {
+    File(File),
+    Atomic(AtomicFile),
+}
+
+impl io::Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::File(file) => file.write(buf),
+            Sink::Atomic(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.flush(),
+            Sink::Atomic(file) => file.flush(),
+        }
+    }
+}
+
+impl<'a, K: Writable, V: Writable> RollingWriter<'a, K, V> {
+    /// Writer of files named `part-NNNNN` in `dir`, each written with `config`. Without
+    /// limits every record goes to a single file.
+    pub fn new<P: AsRef<Path>>(dir: P, config: WriterConfig) -> RollingWriter<'a, K, V> {
+        RollingWriter {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: "part".to_string(),
+            config,
+            max_bytes: None,
+            max_records: None,
+            max_age: None,
+            on_roll: None,
+            temp_location: None,
+            current: None,
+            next_index: 0,
+        }
+    }
+
+    /// Sets the prefix of file names, `part` by default
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets the number of the first file, e.g. to continue after the files of a previous run
+    pub fn first_index(mut self, index: u64) -> Self {
+        self.next_index = index;
+        self
+    }
+
+    /// Rolls a file once its written bytes reach `max_bytes`. With block compression a
+    /// buffered block only counts once it is written, so files overshoot by up to a block.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Rolls a file once it holds `max_records` records
+    pub fn max_records(mut self, max_records: u64) -> Self {
+        self.max_records = Some(max_records);
+        self
+    }
+
+    /// Rolls a file once it has been open for `max_age`. Ages are checked on `append` and
+    /// `roll_if_due`, which an idle stream needs to call periodically.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets a callback run with the path of each completed file
+    pub fn on_roll<F>(mut self, on_roll: F) -> Self
+    where
+        F: FnMut(&Path) + 'a,
+    {
+        self.on_roll = Some(Box::new(on_roll));
+        self
+    }
+
+    /// Writes each file under a temporary name, renaming it once it is rolled, so readers
+    /// only see completed files
+    pub fn temp_location(mut self, location: TempLocation) -> Self {
+        self.temp_location = Some(location);
+        self
+    }
+
+    /// Final path of the file being written, if any record was appended since the last roll
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|current| current.path.as_path())
+    }
+
+    /// Serializes and appends a key-value pair, then rolls the file if it reached a limit
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file already exists or cannot be written
+    pub fn append(&mut self, key: &K, value: &V) -> Result<()> {
+        self.roll_if_due()?;
+        self.current()?.writer.append(key, value)?;
+        self.appended()
+    }
+
+    /// Appends an already serialized key-value pair, then rolls the file if it reached a limit
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file already exists or cannot be written
+    pub fn append_raw(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.roll_if_due()?;
+        self.current()?.writer.append_raw(key, value)?;
+        self.appended()
+    }
+
+    /// Rolls the current file if it reached a limit, returning whether it did
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file cannot be closed
+    pub fn roll_if_due(&mut self) -> Result<bool> {
+        let due = match self.current {
+            Some(ref current) => {
+                self.max_bytes
+                    .is_some_and(|max| current.writer.position() >= max)
+                    || self.max_records.is_some_and(|max| current.records >= max)
+                    || self
+                        .max_age
+                        .is_some_and(|max| current.opened.elapsed() >= max)
+            }
+            None => false,
+        };
+        if due {
+            self.roll()?;
+        }
+        Ok(due)
+    }
+
+    /// Closes the current file, if any, with a final sync marker. The next record starts
+    /// a new file.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file cannot be closed
+    pub fn roll(&mut self) -> Result<()> {
+        let mut current = match self.current.take() {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        current.writer.sync()?;
+        match current.writer.close()? {
+            Sink::File(file) => file.sync_all()?,
+            Sink::Atomic(file) => {
+                file.commit()?;
+            }
+        }
+        if let Some(ref mut on_roll) = self.on_roll {
+            on_roll(&current.path);
+        }
+        Ok(())
+    }
+
+    /// Closes the current file, running the callback for it
+    ///
+    /// # Failures
+    /// Returns an `Error` if the file cannot be closed
+    pub fn close(mut self) -> Result<()> {
+        self.roll()
+    }
+
+    fn current(&mut self) -> Result<&mut Current<K, V>> {
+        if self.current.is_none() {
+            let path = self
+                .dir
+                .join(format!("{}-{:05}", self.prefix, self.next_index));
+            // never replaces a completed file, e.g. of a previous run
+            let sink = match self.temp_location {
+                Some(_) if path.exists() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", path.display()),
+                    )
+                    .into())
+                }
+                Some(location) => Sink::Atomic(AtomicFile::create(&path, location)?),
+                None => Sink::File(
+                    OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)?,
+                ),
+            };
+            self.current = Some(Current {
+                path,
+                writer: Writer::new(sink, self.config.clone())?,
+                records: 0,
+                opened: Instant::now(),
+            });
+            self.next_index += 1;
+        }
+        Ok(self.current.as_mut().expect("opened"))
+    }
+
+    fn appended(&mut self) -> Result<()> {
+        if let Some(ref mut current) = self.current {
+            current.records += 1;
+        }
+        self.roll_if_due().map(|_| ())
+    }
+}
+
+impl<'a, K: Writable, V: Writable> fmt::Debug for RollingWriter<'a, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RollingWriter")
+            .field("dir", &self.dir)
+            .field("prefix", &self.prefix)
+            .field("current", &self.current_path())
+            .field("next_index", &self.next_index)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RollingWriter;
+    use crate::commit::TempLocation;
+    use crate::reader::Reader;
+    use crate::tests::test_config;
+    use crate::verify::Verifier;
+    use crate::{Codec, CompressionType, Text};
+    use std::fs::{self, File};
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile;
+
+    fn keys(path: &Path) -> Vec<i64> {
+        let reader = Reader::<_, i64, Text>::new(File::open(path).unwrap()).unwrap();
+        reader.map(|kv| kv.unwrap().0).collect()
+    }
+
+    #[test]
+    fn rolls_by_records_and_bytes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut completed = Vec::new();
+        {
+            let mut writer = RollingWriter::<i64, Text>::new(dir, test_config())
+                .max_records(40)
+                .on_roll(|path| completed.push(path.to_path_buf()));
+            for i in 0..100 {
+                writer.append(&i, &Text::from("value")).unwrap();
+            }
+            writer.close().unwrap();
+        }
+        assert_eq!(3, completed.len());
+        assert_eq!(dir.join("part-00002"), completed[2]);
+        assert_eq!((40..80).collect::<Vec<_>>(), keys(&completed[1]));
+        assert_eq!((80..100).collect::<Vec<_>>(), keys(&completed[2]));
+
+        let config = test_config()
+            .compression(CompressionType::Block, Codec::Gzip)
+            .block_size(512);
+        let mut writer = RollingWriter::<i64, Text>::new(dir, config)
+            .prefix("blocks")
+            .max_bytes(2048);
+        for i in 0..2000 {
+            writer
+                .append(&i, &Text::from(format!("value-{}", i)))
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut all = Vec::new();
+        let mut files = 0;
+        while dir.join(format!("blocks-{:05}", files)).exists() {
+            let path = dir.join(format!("blocks-{:05}", files));
+            // every file ends with a sync marker
+            let bytes = fs::read(&path).unwrap();
+            let marker = Reader::<_, i64, Text>::new(File::open(&path).unwrap())
+                .unwrap()
+                .header
+                .sync_marker;
+            assert_eq!(&marker[..], &bytes[bytes.len() - 16..]);
+            assert!(Verifier::new()
+                .verify(File::open(&path).unwrap())
+                .unwrap()
+                .problems
+                .is_empty());
+            all.extend(keys(&path));
+            files += 1;
+        }
+        assert!(files > 3);
+        assert_eq!((0..2000).collect::<Vec<_>>(), all);
+    }
+
+    #[test]
+    fn rolls_by_age() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("part-00000"), b"earlier run").unwrap();
+
+        let mut writer =
+            RollingWriter::<i64, Text>::new(dir, test_config()).max_age(Duration::from_millis(20));
+        assert!(writer.append(&0, &Text::from("clobber")).is_err());
+
+        let mut writer = RollingWriter::<i64, Text>::new(dir, test_config())
+            .first_index(1)
+            .max_age(Duration::from_millis(20));
+        writer.append(&1, &Text::from("a")).unwrap();
+        assert!(!writer.roll_if_due().unwrap());
+        thread::sleep(Duration::from_millis(30));
+        assert!(writer.roll_if_due().unwrap());
+        assert_eq!(None, writer.current_path());
+        writer.append(&2, &Text::from("b")).unwrap();
+        assert_eq!(
+            Some(dir.join("part-00002").as_path()),
+            writer.current_path()
+        );
+        writer.close().unwrap();
+
+        let mut writer = RollingWriter::<i64, Text>::new(dir, test_config())
+            .first_index(3)
+            .temp_location(TempLocation::Hidden);
+        writer.append(&3, &Text::from("c")).unwrap();
+        assert!(dir.join(".part-00003.tmp").exists());
+        assert!(!dir.join("part-00003").exists());
+        writer.close().unwrap();
+        assert!(!dir.join(".part-00003.tmp").exists());
+
+        assert_eq!(vec![1], keys(&dir.join("part-00001")));
+        assert_eq!(vec![2], keys(&dir.join("part-00002")));
+        assert_eq!(vec![3], keys(&dir.join("part-00003")));
+    }
+}
@@ -0,0 +1,796 @@
+//! Serde support following Hadoop `DataInput`/`DataOutput` conventions
+//!
+//! Values are encoded the way a hand-written Java `Writable` would encode them:
+//! - integers and floats are fixed width, big-endian
+//! - `bool` is a single byte
+//! - strings and chars are hadoop.io.Text (vint length followed by utf8 bytes)
+//! - byte arrays, sequences and maps are prefixed with an i32 length
+//! - `Option` is a boolean presence byte followed by the value
+//! - structs and tuples are their fields in order, with no framing
+//! - enum variants are written by name as Text, like `WritableUtils.writeEnum`
+//!
+//! The format is not self-describing, so `deserialize_any` is not supported.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::serde::Serde;
+//! # use sequencefile::Reader;
+//! # use serde::{Deserialize, Serialize};
+//! # use std::collections::HashMap;
+//! # use std::fs::File;
+//! #[derive(Deserialize, Serialize)]
+//! struct Value {
+//!     id: i64,
+//!     tags: HashMap<String, String>,
+//! }
+//!
+//! # fn main() -> sequencefile::Result<()> {
+//! # let file = File::open("part-r-00000")?;
+//! let reader = Reader::<File, Serde<String>, Serde<Value>>::new(file)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::serde_crate::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use crate::serde_crate::ser::{self, Serialize};
+use crate::util::ZeroCompress;
+use crate::writable::Writable;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt::Display;
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+/// Adapts any serde type into a `Writable` usable as a `Reader`/`Writer` key or value
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Serde<T>(pub T);
+
+impl<T> Serde<T> {
+    /// Unwraps the inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Serde<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Serde<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Writable for Serde<T> {
+    fn read(buf: &mut impl io::Read) -> Result<Self> {
+        from_reader(buf).map(Serde)
+    }
+
+    fn write(&self, buf: &mut impl io::Write) -> Result<()> {
+        to_writer(buf, &self.0)
+    }
+}
+
+/// Deserializes a value from `DataInput` encoded bytes
+pub fn from_reader<R: io::Read, T: DeserializeOwned>(reader: R) -> Result<T> {
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+/// Serializes a value as `DataOutput` encoded bytes
+pub fn to_writer<W: io::Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer::new(writer))
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serialization(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serialization(msg.to_string())
+    }
+}
+
+/// Serde serializer writing Hadoop `DataOutput` conventions
+#[derive(Debug)]
+pub struct Serializer<W: io::Write> {
+    writer: W,
+}
+
+impl<W: io::Write> Serializer<W> {
+    /// Create a new Serializer over an io::Write
+    pub fn new(writer: W) -> Serializer<W> {
+        Serializer { writer }
+    }
+
+    /// Unwraps the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_len(&mut self, len: Option<usize>) -> Result<()> {
+        match len {
+            Some(len) => Ok(self.writer.write_i32::<BigEndian>(len as i32)?),
+            None => Err(Error::Serialization(
+                "sequences and maps must have a known length".to_string(),
+            )),
+        }
+    }
+
+    fn write_text(&mut self, v: &str) -> Result<()> {
+        crate::text::write_text(&mut self.writer, v)
+    }
+}
+
+impl<W: io::Write> ser::Serializer for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        Ok(self.writer.write_u8(v as u8)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        Ok(self.writer.write_i8(v)?)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        Ok(self.writer.write_i16::<BigEndian>(v)?)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        Ok(self.writer.write_i32::<BigEndian>(v)?)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        Ok(self.writer.write_i64::<BigEndian>(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        Ok(self.writer.write_u8(v)?)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        Ok(self.writer.write_u16::<BigEndian>(v)?)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        Ok(self.writer.write_u32::<BigEndian>(v)?)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        Ok(self.writer.write_u64::<BigEndian>(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        Ok(self.writer.write_f32::<BigEndian>(v)?)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        Ok(self.writer.write_f64::<BigEndian>(v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_text(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_text(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_len(Some(v.len()))?;
+        Ok(self.writer.write_all(v)?)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_bool(false)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.serialize_bool(true)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_text(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.write_text(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.write_text(variant)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.write_text(variant)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<W: io::Write> ser::SerializeSeq for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeTuple for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleStruct for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleVariant for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeMap for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeStruct for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeStructVariant for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Serde deserializer reading Hadoop `DataInput` conventions
+#[derive(Debug)]
+pub struct Deserializer<R: io::Read> {
+    reader: R,
+}
+
+impl<R: io::Read> Deserializer<R> {
+    /// Create a new Deserializer from an io::Read
+    pub fn new(reader: R) -> Deserializer<R> {
+        Deserializer { reader }
+    }
+
+    /// Unwraps the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let len = self.reader.read_i32::<BigEndian>()?;
+        if len < 0 {
+            return Err(Error::Serialization(format!("negative length: {}", len)));
+        }
+        Ok(len as usize)
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_text(&mut self) -> Result<String> {
+        let len = self.reader.decode_vint64()?;
+        if len < 0 {
+            return Err(Error::Serialization(format!("negative length: {}", len)));
+        }
+        let buf = self.read_bytes(len as usize)?;
+        String::from_utf8(buf).map_err(|e| Error::BadEncoding(e.utf8_error()))
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        match self.reader.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(Error::Serialization(format!("invalid boolean: {}", b))),
+        }
+    }
+}
+
+struct Counted<'a, R: io::Read + 'a> {
+    de: &'a mut Deserializer<R>,
+    remaining: usize,
+}
+
+impl<'de, 'a, R: io::Read> de::SeqAccess<'de> for Counted<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de, 'a, R: io::Read> de::MapAccess<'de> for Counted<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de, R: io::Read> de::EnumAccess<'de> for &mut Deserializer<R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let name = self.read_text()?;
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, R: io::Read> de::VariantAccess<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+impl<'de, R: io::Read> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Serialization(
+            "DataInput is not self-describing, deserialize_any is not supported".to_string(),
+        ))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.read_bool()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.reader.read_i8()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(self.reader.read_i16::<BigEndian>()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(self.reader.read_i32::<BigEndian>()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.reader.read_i64::<BigEndian>()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.reader.read_u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.reader.read_u16::<BigEndian>()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.reader.read_u32::<BigEndian>()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.reader.read_u64::<BigEndian>()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(self.reader.read_f32::<BigEndian>()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.reader.read_f64::<BigEndian>()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let text = self.read_text()?;
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::Serialization(format!(
+                "expected a single character, found '{}'",
+                text
+            ))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_text()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_text()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_byte_buf(self.read_bytes(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_byte_buf(self.read_bytes(len)?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.read_bool()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let remaining = self.read_len()?;
+        visitor.visit_seq(Counted {
+            de: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Counted {
+            de: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let remaining = self.read_len()?;
+        visitor.visit_map(Counted {
+            de: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Serialization(
+            "DataInput is not self-describing, ignored values cannot be skipped".to_string(),
+        ))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_reader, to_writer, Serde};
+    use crate::reader::Reader;
+    use crate::serde_crate::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Complex {
+        s1: i64,
+        s2: u8,
+        s3: i16,
+        s4: HashMap<String, String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Everything {
+        flag: bool,
+        c: char,
+        maybe: Option<u16>,
+        nothing: Option<String>,
+        bytes: Vec<u8>,
+        list: Vec<i32>,
+        tuple: (u8, String),
+        shapes: Vec<Shape>,
+        index: BTreeMap<String, u64>,
+    }
+
+    #[test]
+    fn reads_complex_file() {
+        let file = File::open("test_data/complex.seq").unwrap();
+        let reader = Reader::<File, Serde<String>, Serde<Complex>>::new(file).unwrap();
+
+        let kvs: Vec<_> = reader.map(|kv| kv.unwrap()).collect();
+
+        assert_eq!(1000, kvs.len());
+        assert!(kvs.iter().any(|(_, v)| !v.s4.is_empty()));
+    }
+
+    #[test]
+    fn writes_data_output_layout() {
+        let mut s4 = HashMap::new();
+        s4.insert("k".to_string(), "v".to_string());
+        let value = Complex {
+            s1: 1,
+            s2: 2,
+            s3: 3,
+            s4,
+        };
+
+        let mut buf = vec![];
+        to_writer(&mut buf, &value).unwrap();
+
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 3, 0, 0, 0, 1, 1, b'k', 1, b'v'],
+            buf
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut index = BTreeMap::new();
+        index.insert("a".to_string(), 1);
+        let value = Everything {
+            flag: true,
+            c: 'é',
+            maybe: Some(7),
+            nothing: None,
+            bytes: vec![1, 2, 3],
+            list: vec![-1, 0, 1],
+            tuple: (9, "nine".to_string()),
+            shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+            index,
+        };
+
+        let mut buf = vec![];
+        to_writer(&mut buf, &value).unwrap();
+        let read: Everything = from_reader(Cursor::new(buf)).unwrap();
+
+        assert_eq!(value, read);
+    }
+
+    #[test]
+    fn rejects_unknown_variant() {
+        let mut buf = vec![];
+        to_writer(&mut buf, "Hexagon").unwrap();
+
+        assert!(from_reader::<_, Shape>(Cursor::new(buf)).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! External merge sort of sequencefiles, like hadoop.io.SequenceFile.Sorter
+//!
+//! Records are read raw and buffered until the memory limit is reached, then sorted with
+//! the key's `RawComparator` and spilled to a temporary sequencefile. Spilled runs are
+//! k-way merged with a `Merger`, in several passes if there are more runs than the merge factor.
+//! Inputs which fit in memory are sorted without touching the temporary directory.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::sorter::Sorter;
+//! # use sequencefile::writable::LongComparator;
+//! # fn main() -> sequencefile::Result<()> {
+//! let sorter = Sorter::new(LongComparator).memory_limit(64 * 1024 * 1024);
+//! sorter.sort_files(&["part-00000", "part-00001"], "sorted.seq")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::merge::Merger;
+use crate::reader::Reader;
+use crate::writable::RawComparator;
+use crate::writer::{Writer, WriterConfig};
+use crate::{ByteString, CompressionType, Header};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+/// Default bytes of records buffered in memory before a run is spilled, like `io.sort.mb`
+pub const DEFAULT_MEMORY_LIMIT: usize = 100 * 1024 * 1024;
+
+/// Default maximum number of runs merged at once, like `io.sort.factor`
+pub const DEFAULT_MERGE_FACTOR: usize = 100;
+
+// approximate per-record bookkeeping on top of key and value bytes
+const RECORD_OVERHEAD: usize = 64;
+
+static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+type RawRecord = (ByteString, ByteString);
+
+/// Sorts sequencefiles by key using bounded memory
+#[derive(Debug, Clone)]
+pub struct Sorter<C: RawComparator> {
+    comparator: C,
+    memory_limit: usize,
+    merge_factor: usize,
+    tmp_dir: PathBuf,
+}
+
+impl<C: RawComparator> Sorter<C> {
+    /// Create a new Sorter ordering keys with the given raw comparator
+    pub fn new(comparator: C) -> Sorter<C> {
+        Sorter {
+            comparator,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            merge_factor: DEFAULT_MERGE_FACTOR,
+            tmp_dir: env::temp_dir(),
+        }
+    }
+
+    /// Sets the bytes of records held in memory before spilling a sorted run
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Sets the maximum number of runs merged in a single pass, at least 2
+    pub fn merge_factor(mut self, factor: usize) -> Self {
+        self.merge_factor = factor.max(2);
+        self
+    }
+
+    /// Sets the directory spilled runs are written to
+    pub fn tmp_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.tmp_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sorts a single sequencefile into `output`, keeping the input's header settings
+    ///
+    /// # Failures
+    /// Returns an `Error` if the input cannot be read or the output or a spill
+    /// cannot be written
+    pub fn sort<R: io::Read, W: io::Write>(&self, input: R, output: W) -> Result<W> {
+        let reader = Reader::<R, ByteString, ByteString>::new(input)?;
+        let config = WriterConfig::from_header(&reader.header);
+
+        self.sort_records(reader, config, output)
+    }
+
+    /// Sorts several sequencefiles into a single `output` file, keeping the first input's
+    /// header settings
+    ///
+    /// # Failures
+    /// Returns `Error::IncompatibleHeader` if the inputs have different key or value classes
+    pub fn sort_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        inputs: &[P],
+        output: Q,
+    ) -> Result<()> {
+        let mut readers = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            readers.push(Reader::<File, ByteString, ByteString>::new(File::open(
+                input,
+            )?)?);
+        }
+
+        let config = match readers.first() {
+            Some(first) => WriterConfig::from_header(&first.header),
+            None => return Err(Error::IncompatibleHeader("no input files".to_string())),
+        };
+        for reader in &readers {
+            check_classes(&config, &reader.header)?;
+        }
+
+        let out = BufWriter::new(File::create(output)?);
+        self.sort_records(readers.into_iter().flatten(), config, out)?;
+        Ok(())
+    }
+
+    fn sort_records<I, W>(&self, records: I, config: WriterConfig, output: W) -> Result<W>
+    where
+        I: Iterator<Item = Result<RawRecord>>,
+        W: io::Write,
+    {
+        let mut runs = Vec::new();
+        let mut buffer: Vec<RawRecord> = Vec::new();
+        let mut buffered = 0;
+
+        for record in records {
+            let record = record?;
+            buffered += record.0.len() + record.1.len() + RECORD_OVERHEAD;
+            buffer.push(record);
+
+            if buffered >= self.memory_limit {
+                runs.push(self.spill(&mut buffer, &config)?);
+                buffered = 0;
+            }
+        }
+
+        if runs.is_empty() {
+            self.sort_buffer(&mut buffer);
+            let mut writer = Writer::<W, ByteString, ByteString>::new(output, config)?;
+            for (key, value) in buffer {
+                writer.append_raw(&key, &value)?;
+            }
+            return writer.close();
+        }
+
+        if !buffer.is_empty() {
+            runs.push(self.spill(&mut buffer, &config)?);
+        }
+
+        while runs.len() > self.merge_factor {
+            let rest = runs.split_off(self.merge_factor);
+            let run = self.create_run()?;
+            let out = BufWriter::new(File::create(&run.path)?);
+            self.merge_runs(&runs, run_config(&config), out)?;
+            // merged runs hold the earliest records, keep them first for stability
+            runs = Some(run).into_iter().chain(rest).collect();
+        }
+
+        self.merge_runs(&runs, config, output)
+    }
+
+    fn sort_buffer(&self, buffer: &mut [RawRecord]) {
+        let comparator = &self.comparator;
+        buffer.sort_by(|a, b| comparator.compare(&a.0, &b.0));
+    }
+
+    fn spill(&self, buffer: &mut Vec<RawRecord>, config: &WriterConfig) -> Result<Run> {
+        self.sort_buffer(buffer);
+
+        let run = self.create_run()?;
+        let out = BufWriter::new(File::create(&run.path)?);
+        let mut writer = Writer::<_, ByteString, ByteString>::new(out, run_config(config))?;
+        for (key, value) in buffer.drain(..) {
+            writer.append_raw(&key, &value)?;
+        }
+        writer.close()?;
+
+        Ok(run)
+    }
+
+    fn create_run(&self) -> Result<Run> {
+        let path = self.tmp_dir.join(format!(
+            ".sequencefile-sort-{}-{}.seq",
+            process::id(),
+            RUN_COUNTER.fetch_add(1, AtomicOrdering::SeqCst)
+        ));
+        Ok(Run { path })
+    }
+
+    fn merge_runs<W: io::Write>(&self, runs: &[Run], config: WriterConfig, output: W) -> Result<W> {
+        let mut sources = Vec::with_capacity(runs.len());
+        for run in runs {
+            sources.push(Reader::<File, ByteString, ByteString>::new(File::open(
+                &run.path,
+            )?)?);
+        }
+
+        let comparator = &self.comparator;
+        let merged = Merger::with_ordering(sources, move |a: &ByteString, b: &ByteString| {
+            comparator.compare(a, b)
+        });
+
+        let mut writer = Writer::<W, ByteString, ByteString>::new(output, config)?;
+        merged.write_to(&mut writer)?;
+        writer.close()
+    }
+}
+
+fn check_classes(config: &WriterConfig, header: &Header) -> Result<()> {
+    if config.key_class != header.key_class || config.value_class != header.value_class {
+        return Err(Error::IncompatibleHeader(format!(
+            "expected {}/{}, found {}/{}",
+            config.key_class, config.value_class, header.key_class, header.value_class
+        )));
+    }
+    Ok(())
+}
+
+// spilled runs are read back soon after, compressing them isn't worth it
+fn run_config(config: &WriterConfig) -> WriterConfig {
+    WriterConfig {
+        compression_type: CompressionType::None,
+        compression_codec: None,
+        ..config.clone()
+    }
+}
+
+/// A spilled run, deleted once dropped
+#[derive(Debug)]
+struct Run {
+    path: PathBuf,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sorter;
+    use crate::reader::Reader;
+    use crate::tests::test_config;
+    use crate::writable::LongComparator;
+    use crate::writer::{Writer, WriterConfig};
+    use crate::{Codec, CompressionType, Text};
+    use std::fs::{self, File};
+    use std::io::Cursor;
+    use tempfile;
+
+    fn keys(n: i64) -> Vec<i64> {
+        // deterministic shuffle with plenty of duplicates
+        (0..n).map(|i| (i * 7919) % 1009 - 500).collect()
+    }
+
+    fn unsorted(config: WriterConfig, keys: &[i64]) -> Vec<u8> {
+        let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            writer.append(key, &Text::from(i.to_string())).unwrap();
+        }
+        writer.close().unwrap()
+    }
+
+    fn read_sorted(buf: Vec<u8>) -> (crate::Header, Vec<(i64, usize)>) {
+        let reader = Reader::<_, i64, Text>::new(Cursor::new(buf)).unwrap();
+        let header = reader.header.clone();
+        let records = reader
+            .map(|kv| kv.unwrap())
+            .map(|(k, v)| (k, v.to_string().parse().unwrap()))
+            .collect();
+        (header, records)
+    }
+
+    fn assert_sorted_stable(keys: &[i64], records: &[(i64, usize)]) {
+        let mut expected: Vec<(i64, usize)> =
+            keys.iter().enumerate().map(|(i, &k)| (k, i)).collect();
+        expected.sort_by_key(|&(k, _)| k);
+
+        assert_eq!(expected, records);
+    }
+
+    #[test]
+    fn sorts_in_memory() {
+        let keys = keys(2000);
+        let config = test_config()
+            .compression(CompressionType::Block, Codec::Gzip)
+            .metadata("origin", "test");
+        let input = unsorted(config, &keys);
+
+        let output = Sorter::new(LongComparator)
+            .sort(Cursor::new(input), Vec::new())
+            .unwrap();
+        let (header, records) = read_sorted(output);
+
+        assert_sorted_stable(&keys, &records);
+        assert_eq!(CompressionType::Block, header.compression_type);
+        assert_eq!(Some(Codec::Gzip), header.compression_codec);
+        assert_eq!("test", header.metadata["origin"]);
+    }
+
+    #[test]
+    fn sorts_with_spills_and_merge_passes() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let keys = keys(5000);
+        let config = test_config().compression(CompressionType::Record, Codec::Default);
+        let input = unsorted(config, &keys);
+
+        let output = Sorter::new(LongComparator)
+            .memory_limit(16 * 1024)
+            .merge_factor(3)
+            .tmp_dir(dir.path())
+            .sort(Cursor::new(input), Vec::new())
+            .unwrap();
+        let (header, records) = read_sorted(output);
+
+        assert_sorted_stable(&keys, &records);
+        assert_eq!(CompressionType::Record, header.compression_type);
+        assert_eq!(0, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn sorts_multiple_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let keys = keys(3000);
+        let (first, second) = keys.split_at(1000);
+        let inputs = [dir.join("part-00000"), dir.join("part-00001")];
+        fs::write(&inputs[0], unsorted(test_config(), first)).unwrap();
+        fs::write(&inputs[1], unsorted(test_config(), second)).unwrap();
+
+        Sorter::new(LongComparator)
+            .memory_limit(8 * 1024)
+            .tmp_dir(dir)
+            .sort_files(&inputs, dir.join("sorted"))
+            .unwrap();
+
+        let reader = Reader::<_, i64, Text>::new(File::open(dir.join("sorted")).unwrap()).unwrap();
+        let sorted: Vec<i64> = reader.map(|kv| kv.unwrap().0).collect();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(expected, sorted);
+    }
+}
@@ -1,7 +1,10 @@
+use crate::errors::{Error, Result};
+use crate::reader;
+use crate::writer::{Writer, WriterConfig};
+use crate::{Codec, CompressionType, Text};
 use byteorder::{BigEndian, ByteOrder};
-use errors::Result;
-use reader;
 use std::fs::File;
+use std::io::Cursor;
 use std::path::Path;
 
 macro_rules! test_std {
@@ -32,9 +35,8 @@ test_std!(abc_long_text_gzip_block);
 test_std!(abc_long_text_bzip2_record);
 test_std!(abc_long_text_bzip2_block);
 
-// TODO: Snappy support
-// test_std!(abc_long_text_snappy_record);
-// test_std!(abc_long_text_snappy_block);
+test_std!(abc_long_text_snappy_record);
+test_std!(abc_long_text_snappy_block);
 
 #[test]
 fn reads_metadata() {
@@ -55,9 +57,275 @@ fn read_checks_magic() {
     };
 }
 
+macro_rules! test_round_trip {
+    ($name:ident, $compression:expr, $codec:expr) => {
+        #[test]
+        fn $name() {
+            let config = test_config()
+                .compression($compression, $codec)
+                .metadata("a", "b")
+                .block_size(4096)
+                .sync_interval(1024);
+
+            let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+            for i in 0..5000 {
+                writer
+                    .append(&i, &Text::from(format!("value-{}", i)))
+                    .unwrap();
+            }
+            let buf = writer.close().unwrap();
+
+            let sf = reader::Reader::<_, i64, Text>::new(Cursor::new(buf)).unwrap();
+            assert_eq!($compression, sf.header.compression_type);
+            assert_eq!("b", sf.header.metadata.get("a").unwrap());
+
+            let kvs: Vec<(i64, String)> = sf
+                .map(|e| e.unwrap())
+                .map(|(k, v)| (k, v.to_string().to_string()))
+                .collect();
+            assert_eq!(5000, kvs.len());
+            assert_eq!((4999, "value-4999".to_string()), kvs[4999]);
+        }
+    };
+}
+
+test_round_trip!(round_trip_none, CompressionType::None, Codec::Default);
+
+test_round_trip!(
+    round_trip_deflate_record,
+    CompressionType::Record,
+    Codec::Default
+);
+test_round_trip!(
+    round_trip_deflate_block,
+    CompressionType::Block,
+    Codec::Default
+);
+
+test_round_trip!(round_trip_gzip_record, CompressionType::Record, Codec::Gzip);
+test_round_trip!(round_trip_gzip_block, CompressionType::Block, Codec::Gzip);
+
+test_round_trip!(
+    round_trip_bzip2_record,
+    CompressionType::Record,
+    Codec::Bzip2
+);
+test_round_trip!(round_trip_bzip2_block, CompressionType::Block, Codec::Bzip2);
+
+test_round_trip!(
+    round_trip_snappy_record,
+    CompressionType::Record,
+    Codec::Snappy
+);
+test_round_trip!(
+    round_trip_snappy_block,
+    CompressionType::Block,
+    Codec::Snappy
+);
+
+#[test]
+fn rewrites_reference_file() {
+    let sf = reader_for("test_data/abc_long_text_none.seq").unwrap();
+    let config = WriterConfig::from_header(&sf.header);
+    let marker = sf.header.sync_marker.clone();
+    let records: Vec<_> = sf.map(|e| e.unwrap()).collect();
+
+    let mut writer = Writer::<_, Vec<u8>, Vec<u8>>::new(Vec::new(), config).unwrap();
+    for (k, v) in &records {
+        writer.append(k, v).unwrap();
+    }
+    let buf = writer.close().unwrap();
+
+    // identical but for the randomly generated sync marker
+    let original = std::fs::read("test_data/abc_long_text_none.seq").unwrap();
+    let marker_start = original.windows(16).position(|w| w == &marker[..]).unwrap();
+    let marker_end = marker_start + marker.len();
+    assert_eq!(original.len(), buf.len());
+    assert_eq!(original[..marker_start], buf[..marker_start]);
+    assert_eq!(original[marker_end..], buf[marker_end..]);
+}
+
+#[test]
+fn splits_read_every_record_once() {
+    for &compression in &[
+        CompressionType::None,
+        CompressionType::Record,
+        CompressionType::Block,
+    ] {
+        let config = test_config()
+            .compression(compression, Codec::Gzip)
+            .block_size(4096)
+            .sync_interval(1024);
+        let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+        for i in 0..3000 {
+            writer
+                .append(&i, &Text::from(format!("value-{}", i)))
+                .unwrap();
+        }
+        let buf = writer.close().unwrap();
+
+        let len = buf.len() as u64;
+        for &splits in &[1, 2, 3, 7, 50] {
+            let mut keys = Vec::new();
+            for i in 0..splits {
+                let mut sf = reader::Reader::<_, i64, Text>::new(Cursor::new(&buf)).unwrap();
+                sf.split(len * i / splits, len * (i + 1) / splits).unwrap();
+                keys.extend(sf.map(|e| e.unwrap().0));
+            }
+            assert_eq!(
+                (0..3000).collect::<Vec<_>>(),
+                keys,
+                "{:?} in {} splits",
+                compression,
+                splits
+            );
+        }
+
+        let mut sf = reader::Reader::<_, i64, Text>::new(Cursor::new(&buf)).unwrap();
+        assert!(!sf.sync(len - 10).unwrap());
+        assert_eq!(0, sf.count());
+    }
+}
+
+#[test]
+fn appends_to_existing_files() {
+    for &compression in &[
+        CompressionType::None,
+        CompressionType::Record,
+        CompressionType::Block,
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("append.seq");
+        let config = test_config()
+            .compression(compression, Codec::Gzip)
+            .block_size(4096)
+            .sync_interval(1024);
+
+        for batch in 0..3 {
+            let mut writer =
+                Writer::<_, i64, Text>::append_if_exists(&path, config.clone()).unwrap();
+            for i in batch * 1000..(batch + 1) * 1000 {
+                writer
+                    .append(&i, &Text::from(format!("value-{}", i)))
+                    .unwrap();
+            }
+            writer.close().unwrap();
+        }
+
+        // a record or block torn by a crash is an error, or dropped on request
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut writer = Writer::<_, i64, Text>::append_if_exists(&path, config.clone()).unwrap();
+        writer.append(&-1, &Text::from("torn")).unwrap();
+        writer.close().unwrap();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(std::fs::metadata(&path).unwrap().len() - 5)
+            .unwrap();
+        drop(file);
+        let torn_len = std::fs::metadata(&path).unwrap().len();
+        match Writer::<_, i64, Text>::append_if_exists(&path, config.clone()) {
+            Err(Error::BadRecord(_)) => (),
+            other => panic!("expected a torn tail, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(torn_len, std::fs::metadata(&path).unwrap().len());
+        let truncate = config.clone().truncate_torn_tail(true);
+        let mut writer = Writer::<_, i64, Text>::append_if_exists(&path, truncate).unwrap();
+        assert!(writer.position() <= len + 20);
+        writer.append(&3000, &Text::from("value-3000")).unwrap();
+        writer.close().unwrap();
+
+        let sf = reader::Reader::<_, i64, Text>::new(File::open(&path).unwrap()).unwrap();
+        let keys: Vec<i64> = sf.map(|e| e.unwrap().0).collect();
+        assert_eq!((0..3001).collect::<Vec<_>>(), keys, "{:?}", compression);
+    }
+}
+
+#[test]
+fn append_checks_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("append.seq");
+    let config = test_config().compression(CompressionType::Block, Codec::Snappy);
+    let writer = Writer::<_, i64, Text>::new(File::create(&path).unwrap(), config.clone());
+    writer.unwrap().close().unwrap();
+
+    let codec = config
+        .clone()
+        .compression(CompressionType::Block, Codec::Gzip);
+    let classes = WriterConfig {
+        value_class: "org.apache.hadoop.io.BytesWritable".to_string(),
+        ..config.clone()
+    };
+    let results = [
+        Writer::<_, i64, Text>::append_if_exists(&path, codec).map(|_| ()),
+        Writer::<_, i64, Text>::append_if_exists(&path, classes).map(|_| ()),
+        Writer::<_, i64, Text>::append_if_exists(&path, config).map(|_| ()),
+    ];
+    assert!(matches!(results[0], Err(Error::IncompatibleHeader(_))));
+    assert!(matches!(results[1], Err(Error::IncompatibleHeader(_))));
+    assert!(results[2].is_ok());
+}
+
+#[test]
+fn hsync_bounds_records_lost_in_a_crash() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hsync.seq");
+    let config = test_config().compression(CompressionType::Block, Codec::Default);
+    let read_keys = || -> Vec<i64> {
+        let sf = reader::Reader::<_, i64, Text>::new(File::open(&path).unwrap()).unwrap();
+        sf.map(|e| e.unwrap().0).collect()
+    };
+
+    let mut writer = Writer::<_, i64, Text>::new(File::create(&path).unwrap(), config).unwrap();
+    for i in 0..10 {
+        writer.append(&i, &Text::from("value")).unwrap();
+    }
+    writer.hflush().unwrap();
+    assert_eq!((0..10).collect::<Vec<_>>(), read_keys());
+
+    let position = writer.position();
+    writer.sync().unwrap();
+    writer.sync().unwrap();
+    assert_eq!(position + 20, writer.position());
+
+    for i in 10..20 {
+        writer.append(&i, &Text::from("value")).unwrap();
+    }
+    writer.hsync().unwrap();
+    for i in 20..30 {
+        writer.append(&i, &Text::from("value")).unwrap();
+    }
+    // a crash: the buffered block is never written
+    std::mem::forget(writer);
+
+    assert_eq!((0..20).collect::<Vec<_>>(), read_keys());
+}
+
+/// LongWritable keys and Text values, the classes most tests write
+pub fn test_config() -> WriterConfig {
+    WriterConfig::new(
+        "org.apache.hadoop.io.LongWritable",
+        "org.apache.hadoop.io.Text",
+    )
+}
+
+/// LongWritable keys and values
+pub fn long_config() -> WriterConfig {
+    WriterConfig::new(
+        "org.apache.hadoop.io.LongWritable",
+        "org.apache.hadoop.io.LongWritable",
+    )
+}
+
+/// Text keys and LongWritable values
+pub fn text_long_config() -> WriterConfig {
+    WriterConfig::new(
+        "org.apache.hadoop.io.Text",
+        "org.apache.hadoop.io.LongWritable",
+    )
+}
+
 fn reader_for(filename: &str) -> Result<reader::Reader<File, Vec<u8>, Vec<u8>>> {
     let path = Path::new(filename);
-    let file = File::open(&path)?;
+    let file = File::open(path)?;
 
     reader::Reader::new(file)
 }
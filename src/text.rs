@@ -1,11 +1,15 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, cmp::Ordering, fmt::Display};
 
-use crate::{errors::Result, read_vint, writable::Writable};
+use crate::{
+    errors::Result,
+    read_vint,
+    writable::{TextComparator, VarInt, Writable, WritableComparable},
+};
 
 /// hadoop.io.Text
 /// warning -- utf8 special is not implemented
 /// will deliver proper results only if underlying string is 'simple' enough
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Text {
     len: i32,
     buf: Vec<u8>,
@@ -13,7 +17,7 @@ pub struct Text {
 
 impl Text {
     /// Converts to String
-    pub fn to_string(&self) -> Cow<str> {
+    pub fn to_string(&self) -> Cow<'_, str> {
         String::from_utf8_lossy(&self.buf)
     }
 
@@ -30,6 +34,74 @@ impl Writable for Text {
         input.read_exact(&mut buf)?;
         Ok(Self { len, buf })
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        self.len.write_vint(buf)?;
+        buf.write_all(&self.buf)?;
+        Ok(())
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.buf == other.buf
+    }
+}
+
+impl Eq for Text {}
+
+impl PartialOrd for Text {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by utf8 bytes, as hadoop.io.Text does
+impl Ord for Text {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.buf.cmp(&other.buf)
+    }
+}
+
+impl WritableComparable for Text {
+    type Comparator = TextComparator;
+}
+
+impl<'a> From<&'a str> for Text {
+    fn from(s: &'a str) -> Self {
+        Self {
+            len: s.len() as i32,
+            buf: s.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Self {
+        Self {
+            len: s.len() as i32,
+            buf: s.into_bytes(),
+        }
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        String::from_utf8(text.buf)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+    }
+}
+
+/// Reads a hadoop.io.Text straight into a String
+pub fn read_text(buf: &mut impl std::io::Read) -> Result<String> {
+    Text::read(buf).map(String::from)
+}
+
+/// Writes a string using hadoop.io.Text encoding
+pub fn write_text(buf: &mut impl std::io::Write, s: &str) -> Result<()> {
+    (s.len() as i32).write_vint(buf)?;
+    buf.write_all(s.as_bytes())?;
+    Ok(())
 }
 
 impl Display for Text {
@@ -1,5 +1,5 @@
-use errors::Result;
-use std::io::Read;
+use crate::errors::Result;
+use std::io::{Read, Write};
 
 pub trait ZeroCompress: Read {
     fn decode_vint64(&mut self) -> Result<i64> {
@@ -17,19 +17,19 @@ pub trait ZeroCompress: Read {
             -111 - value
         };
 
-        let mut val = 0i64;
         if len == 1 {
-            val = value as i64;
-        } else {
-            // shifts buffer to make room for new set-o-bits, ors in the new byte
-            for _ in 0..(len - 1) {
-                self.read_exact(&mut raw_buffer)?;
-                val <<= 8;
-                val |= raw_buffer[0] as i64 & 0xFF
-            }
+            return Ok(value as i64);
+        }
+
+        // shifts buffer to make room for new set-o-bits, ors in the new byte
+        let mut val = 0i64;
+        for _ in 0..(len - 1) {
+            self.read_exact(&mut raw_buffer)?;
+            val <<= 8;
+            val |= raw_buffer[0] as i64 & 0xFF
         }
 
-        if value < -120 || (value >= -112 && value < 0) {
+        if value < -120 {
             Ok(val ^ -1)
         } else {
             Ok(val)
@@ -39,9 +39,61 @@ pub trait ZeroCompress: Read {
 
 impl<R> ZeroCompress for R where R: Read {}
 
+pub trait ZeroCompressWrite: Write {
+    fn encode_vint64(&mut self, value: i64) -> Result<()> {
+        if (-112..=127).contains(&value) {
+            self.write_all(&[value as u8])?;
+            return Ok(());
+        }
+
+        let (mut len, val) = if value < 0 {
+            (-120, value ^ -1)
+        } else {
+            (-112, value)
+        };
+
+        let mut tmp = val;
+        while tmp != 0 {
+            tmp >>= 8;
+            len -= 1;
+        }
+        self.write_all(&[len as i8 as u8])?;
+
+        let len = if len < -120 {
+            -(len + 120)
+        } else {
+            -(len + 112)
+        };
+        for idx in (0..len).rev() {
+            self.write_all(&[(val >> (idx * 8)) as u8])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W> ZeroCompressWrite for W where W: Write {}
+
+/// Sorted data files of a job output directory, skipping hidden files and files starting
+/// with `_`, e.g. `_SUCCESS`
+pub fn part_files(dir: &::std::path::Path) -> Result<Vec<::std::path::PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in ::std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || name.starts_with('_') || !entry.file_type()?.is_file() {
+            continue;
+        }
+        paths.push(entry.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::ZeroCompress;
+    use super::{ZeroCompress, ZeroCompressWrite};
     use std::io::Cursor;
 
     #[test]
@@ -64,4 +116,37 @@ mod tests {
 
         assert_eq!(-8_405_038, buf.decode_vint64().unwrap());
     }
+
+    #[test]
+    fn encodes_round_trip() {
+        for &v in &[
+            0,
+            1,
+            -1,
+            127,
+            -112,
+            128,
+            -113,
+            8_405_037,
+            -8_405_038,
+            i64::MAX,
+            i64::MIN,
+        ] {
+            let mut buf = vec![];
+            buf.encode_vint64(v).unwrap();
+
+            assert_eq!(v, Cursor::new(buf).decode_vint64().unwrap());
+        }
+    }
+
+    #[test]
+    fn encodes_multi_byte() {
+        let mut buf = vec![];
+        buf.encode_vint64(8_405_037).unwrap();
+
+        assert_eq!(
+            vec![0b1000_1101, 0b1000_0000, 0b0100_0000, 0b0010_1101],
+            buf
+        );
+    }
 }
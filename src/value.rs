@@ -0,0 +1,238 @@
+//! Dynamically typed values of the common Hadoop Writables, decoded by the Java class
+//! names found in a header. Used to render files whose types are only known at runtime.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::value::Value;
+//! # use sequencefile::Reader;
+//! # use std::fs::File;
+//! # fn main() -> sequencefile::Result<()> {
+//! # let file = File::open("part-r-00000")?;
+//! let seqfile = Reader::<_, Vec<u8>, Vec<u8>>::new(file)?;
+//! let (key_class, value_class) = (seqfile.header.key_class.clone(), seqfile.header.value_class.clone());
+//! for kv in seqfile {
+//!     let (key, value) = kv?;
+//!     println!("{}\t{}", Value::decode(&key_class, &key)?, Value::decode(&value_class, &value)?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::text::{read_text, write_text};
+use crate::writable::{BytesWritable, VarInt, Writable};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::io::Cursor;
+
+/// org.apache.hadoop.io.NullWritable
+pub const NULL_WRITABLE: &str = "org.apache.hadoop.io.NullWritable";
+/// org.apache.hadoop.io.BooleanWritable
+pub const BOOLEAN_WRITABLE: &str = "org.apache.hadoop.io.BooleanWritable";
+/// org.apache.hadoop.io.ByteWritable
+pub const BYTE_WRITABLE: &str = "org.apache.hadoop.io.ByteWritable";
+/// org.apache.hadoop.io.ShortWritable
+pub const SHORT_WRITABLE: &str = "org.apache.hadoop.io.ShortWritable";
+/// org.apache.hadoop.io.IntWritable
+pub const INT_WRITABLE: &str = "org.apache.hadoop.io.IntWritable";
+/// org.apache.hadoop.io.VIntWritable
+pub const VINT_WRITABLE: &str = "org.apache.hadoop.io.VIntWritable";
+/// org.apache.hadoop.io.LongWritable
+pub const LONG_WRITABLE: &str = "org.apache.hadoop.io.LongWritable";
+/// org.apache.hadoop.io.VLongWritable
+pub const VLONG_WRITABLE: &str = "org.apache.hadoop.io.VLongWritable";
+/// org.apache.hadoop.io.FloatWritable
+pub const FLOAT_WRITABLE: &str = "org.apache.hadoop.io.FloatWritable";
+/// org.apache.hadoop.io.DoubleWritable
+pub const DOUBLE_WRITABLE: &str = "org.apache.hadoop.io.DoubleWritable";
+/// org.apache.hadoop.io.Text
+pub const TEXT: &str = "org.apache.hadoop.io.Text";
+/// org.apache.hadoop.io.BytesWritable
+pub const BYTES_WRITABLE: &str = "org.apache.hadoop.io.BytesWritable";
+
+/// A decoded Writable, `Raw` holds the serialized bytes of classes not known here
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// NullWritable
+    Null,
+    /// BooleanWritable
+    Boolean(bool),
+    /// ByteWritable
+    Byte(i8),
+    /// ShortWritable
+    Short(i16),
+    /// IntWritable and VIntWritable
+    Int(i32),
+    /// LongWritable and VLongWritable
+    Long(i64),
+    /// FloatWritable
+    Float(f32),
+    /// DoubleWritable
+    Double(f64),
+    /// Text
+    Text(String),
+    /// BytesWritable
+    Bytes(Vec<u8>),
+    /// any other Writable, as serialized
+    Raw(Vec<u8>),
+}
+
+impl Value {
+    /// Decodes the serialized form of a Writable of the given Java class
+    ///
+    /// # Failures
+    /// Returns an `Error` if the bytes are too short or not valid for a known class
+    pub fn decode(class: &str, bytes: &[u8]) -> Result<Value> {
+        let mut buf = Cursor::new(bytes);
+        let value = match class {
+            NULL_WRITABLE => Value::Null,
+            BOOLEAN_WRITABLE => Value::Boolean(buf.read_u8()? != 0),
+            BYTE_WRITABLE => Value::Byte(buf.read_i8()?),
+            SHORT_WRITABLE => Value::Short(buf.read_i16::<BigEndian>()?),
+            INT_WRITABLE => Value::Int(buf.read_i32::<BigEndian>()?),
+            VINT_WRITABLE => Value::Int(i32::read_vint(&mut buf)?),
+            LONG_WRITABLE => Value::Long(buf.read_i64::<BigEndian>()?),
+            VLONG_WRITABLE => Value::Long(i64::read_vint(&mut buf)?),
+            FLOAT_WRITABLE => Value::Float(buf.read_f32::<BigEndian>()?),
+            DOUBLE_WRITABLE => Value::Double(buf.read_f64::<BigEndian>()?),
+            TEXT => Value::Text(read_text(&mut buf)?),
+            BYTES_WRITABLE => Value::Bytes(BytesWritable::read(&mut buf)?.0),
+            _ => Value::Raw(bytes.to_vec()),
+        };
+        Ok(value)
+    }
+
+    /// Serializes the value as a Writable of the given Java class, the inverse of `decode`.
+    /// `Raw` bytes are written as they are for classes not known here.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the value doesn't fit the class
+    pub fn encode(&self, class: &str) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match (class, self) {
+            (NULL_WRITABLE, Value::Null) => (),
+            (BOOLEAN_WRITABLE, &Value::Boolean(v)) => (v as u8).write(&mut buf)?,
+            (BYTE_WRITABLE, Value::Byte(v)) => v.write(&mut buf)?,
+            (SHORT_WRITABLE, Value::Short(v)) => v.write(&mut buf)?,
+            (INT_WRITABLE, Value::Int(v)) => v.write(&mut buf)?,
+            (VINT_WRITABLE, Value::Int(v)) => v.write_vint(&mut buf)?,
+            (LONG_WRITABLE, Value::Long(v)) => v.write(&mut buf)?,
+            (VLONG_WRITABLE, Value::Long(v)) => v.write_vint(&mut buf)?,
+            (FLOAT_WRITABLE, &Value::Float(v)) => buf.write_f32::<BigEndian>(v)?,
+            (DOUBLE_WRITABLE, &Value::Double(v)) => buf.write_f64::<BigEndian>(v)?,
+            (TEXT, Value::Text(v)) => write_text(&mut buf, v)?,
+            (BYTES_WRITABLE, Value::Bytes(v)) => BytesWritable(v.clone()).write(&mut buf)?,
+            (class, Value::Raw(v)) if !Value::is_known(class) => buf.extend_from_slice(v),
+            (class, value) => {
+                return Err(Error::Serialization(format!(
+                    "cannot encode {:?} as {}",
+                    value, class
+                )))
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Tells if `decode` understands the Java class
+    pub fn is_known(class: &str) -> bool {
+        matches!(
+            class,
+            NULL_WRITABLE
+                | BOOLEAN_WRITABLE
+                | BYTE_WRITABLE
+                | SHORT_WRITABLE
+                | INT_WRITABLE
+                | VINT_WRITABLE
+                | LONG_WRITABLE
+                | VLONG_WRITABLE
+                | FLOAT_WRITABLE
+                | DOUBLE_WRITABLE
+                | TEXT
+                | BYTES_WRITABLE
+        )
+    }
+}
+
+/// Renders like the Writable's `toString`, bytes as space separated hex
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "(null)"),
+            Value::Boolean(v) => write!(f, "{}", v),
+            Value::Byte(v) => write!(f, "{}", v),
+            Value::Short(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Long(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::Double(v) => write!(f, "{:?}", v),
+            Value::Text(v) => write!(f, "{}", v),
+            Value::Bytes(bytes) | Value::Raw(bytes) => {
+                for (i, b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Value, BYTES_WRITABLE, DOUBLE_WRITABLE, LONG_WRITABLE, TEXT, VINT_WRITABLE};
+    use crate::writable::{BytesWritable, VarInt, Writable};
+
+    #[test]
+    fn decodes_known_classes() {
+        let mut buf = Vec::new();
+        42i64.write(&mut buf).unwrap();
+        assert_eq!(Value::Long(42), Value::decode(LONG_WRITABLE, &buf).unwrap());
+
+        let mut buf = Vec::new();
+        (-300i32).write_vint(&mut buf).unwrap();
+        assert_eq!(
+            Value::Int(-300),
+            Value::decode(VINT_WRITABLE, &buf).unwrap()
+        );
+
+        let mut buf = Vec::new();
+        crate::Text::from("héllo").write(&mut buf).unwrap();
+        let text = Value::decode(TEXT, &buf).unwrap();
+        assert_eq!("héllo", text.to_string());
+
+        let mut buf = Vec::new();
+        BytesWritable(vec![0xde, 0xad, 0x01])
+            .write(&mut buf)
+            .unwrap();
+        let bytes = Value::decode(BYTES_WRITABLE, &buf).unwrap();
+        assert_eq!("de ad 01", bytes.to_string());
+
+        assert_eq!("1.0", Value::Double(1.0).to_string());
+        assert!(Value::decode(DOUBLE_WRITABLE, &[0; 4]).is_err());
+        assert_eq!(
+            Value::Raw(vec![1, 2]),
+            Value::decode("com.example.Custom", &[1, 2]).unwrap()
+        );
+        assert!(!Value::is_known("com.example.Custom"));
+    }
+
+    #[test]
+    fn encodes_known_classes() {
+        for &(class, ref value) in &[
+            (LONG_WRITABLE, Value::Long(-42)),
+            (VINT_WRITABLE, Value::Int(300)),
+            (DOUBLE_WRITABLE, Value::Double(0.25)),
+            (TEXT, Value::Text("héllo".to_string())),
+            (BYTES_WRITABLE, Value::Bytes(vec![1, 2, 3])),
+            ("com.example.Custom", Value::Raw(vec![9, 9])),
+        ] {
+            let bytes = value.encode(class).unwrap();
+            assert_eq!(*value, Value::decode(class, &bytes).unwrap());
+        }
+
+        assert!(Value::Text("1".to_string()).encode(LONG_WRITABLE).is_err());
+        assert!(Value::Raw(vec![0; 8]).encode(LONG_WRITABLE).is_err());
+    }
+}
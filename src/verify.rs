@@ -0,0 +1,593 @@
+//! Whole-file verification, an fsck for sequencefiles. Unlike `Reader`, which stops at the
+//! first error, the verifier records each problem with its byte offset and carries on: with
+//! the next record when only a record's contents are bad, otherwise after skipping ahead to
+//! the next sync marker.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::verify::Verifier;
+//! # use sequencefile::Text;
+//! # use std::fs::File;
+//! # fn main() -> sequencefile::Result<()> {
+//! # let path = "part-r-00000";
+//! let report = Verifier::new().decode::<Text, i64>().verify(File::open(path)?)?;
+//! for problem in &report.problems {
+//!     println!("{}", problem);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::compress;
+use crate::errors::{Error, Result};
+use crate::reader::{read_header, SYNC_SIZE};
+use crate::util::ZeroCompress;
+use crate::writable::Writable;
+use crate::{CompressionType, Header};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::fmt;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+
+type RecordCheck<'a> = Box<dyn FnMut(&[u8], &[u8]) -> Result<()> + 'a>;
+
+/// A problem found in a file
+#[derive(Debug)]
+pub struct Problem {
+    /// Byte offset of the record, block or sync marker at fault
+    pub offset: u64,
+    /// What is wrong with it
+    pub error: Error,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.error)
+    }
+}
+
+/// Outcome of verifying a file
+#[derive(Debug)]
+pub struct Report {
+    /// Header of the file
+    pub header: Header,
+    /// Records read successfully
+    pub records: u64,
+    /// Blocks read successfully, with `CompressionType::Block`
+    pub blocks: u64,
+    /// Sync markers found
+    pub sync_markers: u64,
+    /// Bytes skipped while looking for a sync marker after a problem
+    pub skipped_bytes: u64,
+    /// Problems, in file order
+    pub problems: Vec<Problem>,
+    /// Set when verification stopped early after reaching the problem limit
+    pub truncated: bool,
+}
+
+impl Report {
+    /// Tells if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Walks a file checking sync markers, record lengths, block sections, decompression
+/// and, optionally, that keys and values decode
+pub struct Verifier<'a> {
+    check: Option<RecordCheck<'a>>,
+    max_problems: usize,
+}
+
+impl<'a> Default for Verifier<'a> {
+    fn default() -> Self {
+        Verifier::new()
+    }
+}
+
+impl<'a> Verifier<'a> {
+    /// Verifier of the file structure only
+    pub fn new() -> Verifier<'a> {
+        Verifier {
+            check: None,
+            max_problems: 100,
+        }
+    }
+
+    /// Also checks that every key and value reads as `K` and `V`, with no trailing bytes
+    pub fn decode<K: Writable, V: Writable>(self) -> Self {
+        self.check_records(|key, value| {
+            fully_read::<K>(key, "key")?;
+            fully_read::<V>(value, "value")
+        })
+    }
+
+    /// Also runs a check over every raw key and uncompressed value
+    pub fn check_records<F>(mut self, check: F) -> Self
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<()> + 'a,
+    {
+        self.check = Some(Box::new(check));
+        self
+    }
+
+    /// Stops after this many problems, defaults to 100
+    pub fn max_problems(mut self, max_problems: usize) -> Self {
+        self.max_problems = max_problems.max(1);
+        self
+    }
+
+    /// Verifies a whole file
+    ///
+    /// # Failures
+    /// Returns an `Error` if the header cannot be read, any later problem is reported
+    pub fn verify<R: Read + Seek>(&mut self, input: R) -> Result<Report> {
+        let mut input = Scan::new(input)?;
+        let header = read_header(&mut input)?;
+
+        let mut report = Report {
+            header,
+            records: 0,
+            blocks: 0,
+            sync_markers: 0,
+            skipped_bytes: 0,
+            problems: Vec::new(),
+            truncated: false,
+        };
+
+        let mut after_sync = false;
+        loop {
+            let start = input.position;
+            let unit = match self.next_unit(&mut input, &mut report, start, after_sync) {
+                Ok(unit) => Some(unit),
+                Err(error) => {
+                    report.problems.push(Problem {
+                        offset: start,
+                        error,
+                    });
+                    None
+                }
+            };
+            if report.problems.len() >= self.max_problems {
+                report.problems.truncate(self.max_problems);
+                report.truncated = true;
+                break;
+            }
+
+            match unit {
+                Some(Unit::End) => break,
+                Some(Unit::Sync) => after_sync = true,
+                Some(Unit::Data) => after_sync = false,
+                // the record or block framing is broken, records resume at a sync marker
+                None => match input.resync(start + 1, &report.header.sync_marker)? {
+                    Some(skipped) => {
+                        report.skipped_bytes += skipped;
+                        report.sync_markers += 1;
+                        after_sync = true;
+                    }
+                    None => {
+                        report.skipped_bytes += input.len.saturating_sub(start + 1);
+                        break;
+                    }
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    // reads the next record, block or sync marker, failing only when its framing is broken:
+    // problems with the contents of an intact record or block are added to `report`
+    fn next_unit<R: Read + Seek>(
+        &mut self,
+        input: &mut Scan<R>,
+        report: &mut Report,
+        start: u64,
+        after_sync: bool,
+    ) -> Result<Unit> {
+        let block = report.header.compression_type == CompressionType::Block;
+        // a file may end with a sync marker, e.g. written on close
+        if block && after_sync && input.position >= input.len {
+            return Ok(Unit::End);
+        }
+        if block && after_sync {
+            self.read_block(input, report, start)?;
+            return Ok(Unit::Data);
+        }
+
+        let length = match input.read_length()? {
+            Some(length) => length,
+            None => return Ok(Unit::End),
+        };
+
+        if length == -1 {
+            let mut marker = [0; SYNC_SIZE];
+            input.read_exact(&mut marker)?;
+            if marker[..] != report.header.sync_marker[..] {
+                return Err(Error::SyncMarkerMismatch);
+            }
+            report.sync_markers += 1;
+            return Ok(Unit::Sync);
+        }
+
+        if block {
+            return Err(Error::BadRecord(format!(
+                "expected a sync marker before the block, found length {}",
+                length
+            )));
+        }
+        self.read_record(input, report, start, length)?;
+        Ok(Unit::Data)
+    }
+
+    fn read_record<R: Read + Seek>(
+        &mut self,
+        input: &mut Scan<R>,
+        report: &mut Report,
+        start: u64,
+        length: i32,
+    ) -> Result<()> {
+        let key_length = input.read_i32::<BigEndian>()?;
+        if length < 0 || key_length < 0 || key_length > length {
+            return Err(Error::BadRecord(format!(
+                "bad record length {} with key length {}",
+                length, key_length
+            )));
+        }
+
+        let record = input.read_bytes(length as u64)?;
+        let (key, value) = record.split_at(key_length as usize);
+        let checked = match report.header.compression_type {
+            CompressionType::Record => {
+                let codec = report
+                    .header
+                    .compression_codec
+                    .unwrap_or(crate::Codec::Default);
+                compress::decompressor(&codec, value)
+                    .and_then(|value| self.check_record(key, &value))
+            }
+            _ => self.check_record(key, value),
+        };
+
+        match checked {
+            Ok(()) => report.records += 1,
+            Err(error) => report.problems.push(Problem {
+                offset: start,
+                error,
+            }),
+        }
+        Ok(())
+    }
+
+    fn read_block<R: Read + Seek>(
+        &mut self,
+        input: &mut Scan<R>,
+        report: &mut Report,
+        start: u64,
+    ) -> Result<()> {
+        let codec = report
+            .header
+            .compression_codec
+            .unwrap_or(crate::Codec::Default);
+        let count = input.decode_vint64()?;
+        if count < 0 {
+            return Err(Error::BadRecord(format!(
+                "bad block record count {}",
+                count
+            )));
+        }
+
+        let mut sections = Vec::with_capacity(4);
+        for _ in 0..4 {
+            let length = input.decode_vint64()?;
+            if length < 0 {
+                return Err(Error::BadRecord(format!(
+                    "bad block section length {}",
+                    length
+                )));
+            }
+            sections.push(input.read_bytes(length as u64)?);
+        }
+
+        // the block is framed correctly from here on, its problems don't need a resync
+        let problem = |error| Problem {
+            offset: start,
+            error,
+        };
+        let sections = match sections
+            .iter()
+            .map(|compressed| compress::decompressor(&codec, compressed))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(sections) => sections,
+            Err(error) => {
+                report.problems.push(problem(error));
+                return Ok(());
+            }
+        };
+        let lengths =
+            lengths(&sections[0], count as usize, sections[1].len(), "key").and_then(|keys| {
+                let values = lengths(&sections[2], count as usize, sections[3].len(), "value")?;
+                Ok((keys, values))
+            });
+        let (key_lengths, value_lengths) = match lengths {
+            Ok(lengths) => lengths,
+            Err(error) => {
+                report.problems.push(problem(error));
+                return Ok(());
+            }
+        };
+
+        let (mut keys, mut values) = (&sections[1][..], &sections[3][..]);
+        for (key_length, value_length) in key_lengths.into_iter().zip(value_lengths) {
+            let (key, rest) = keys.split_at(key_length);
+            keys = rest;
+            let (value, rest) = values.split_at(value_length);
+            values = rest;
+            match self.check_record(key, value) {
+                Ok(()) => report.records += 1,
+                Err(error) => report.problems.push(problem(error)),
+            }
+        }
+
+        report.blocks += 1;
+        Ok(())
+    }
+
+    fn check_record(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        match self.check {
+            Some(ref mut check) => check(key, value),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Verifier<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("checking", &self.check.is_some())
+            .field("max_problems", &self.max_problems)
+            .finish()
+    }
+}
+
+enum Unit {
+    Data,
+    Sync,
+    End,
+}
+
+fn fully_read<T: Writable>(bytes: &[u8], what: &str) -> Result<()> {
+    let mut buf = Cursor::new(bytes);
+    T::read(&mut buf)?;
+    let trailing = bytes.len() as u64 - buf.position();
+    if trailing > 0 {
+        return Err(Error::Serialization(format!(
+            "{} has {} trailing bytes",
+            what, trailing
+        )));
+    }
+    Ok(())
+}
+
+// decodes a block's vint lengths, which must add up to the size of their data section
+fn lengths(section: &[u8], count: usize, total: usize, what: &str) -> Result<Vec<usize>> {
+    let mut buf = Cursor::new(section);
+    let mut lengths = Vec::with_capacity(count.min(section.len()));
+    let mut sum = 0u64;
+    for _ in 0..count {
+        let length = buf.decode_vint64()?;
+        if length < 0 {
+            return Err(Error::BadRecord(format!("bad {} length {}", what, length)));
+        }
+        sum += length as u64;
+        lengths.push(length as usize);
+    }
+
+    if sum != total as u64 {
+        return Err(Error::BadRecord(format!(
+            "{} lengths add up to {} bytes, found {}",
+            what, sum, total
+        )));
+    }
+    Ok(lengths)
+}
+
+// buffered input tracking its position and the total length, so lengths read from the
+// file can be checked before allocating
+struct Scan<R: Read + Seek> {
+    inner: BufReader<R>,
+    position: u64,
+    len: u64,
+}
+
+impl<R: Read + Seek> Scan<R> {
+    fn new(mut inner: R) -> Result<Scan<R>> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+        Ok(Scan {
+            inner: BufReader::new(inner),
+            position: 0,
+            len,
+        })
+    }
+
+    // None at a clean end of file
+    fn read_length(&mut self) -> Result<Option<i32>> {
+        if self.position == self.len {
+            return Ok(None);
+        }
+        Ok(Some(self.read_i32::<BigEndian>()?))
+    }
+
+    fn read_bytes(&mut self, length: u64) -> Result<Vec<u8>> {
+        if length > self.len - self.position {
+            return Err(Error::BadRecord(format!(
+                "length {} runs past the end of the file",
+                length
+            )));
+        }
+        let mut bytes = vec![0; length as usize];
+        self.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // positions the input after the next sync escape and marker at or after `from`,
+    // returning the number of bytes skipped, or None if there is none
+    fn resync(&mut self, from: u64, marker: &[u8]) -> Result<Option<u64>> {
+        self.inner.seek(SeekFrom::Start(from))?;
+        self.position = from;
+
+        let size = 4 + SYNC_SIZE;
+        let mut window = Vec::with_capacity(size);
+        let mut byte = [0; 1];
+        while self.read(&mut byte)? == 1 {
+            if window.len() == size {
+                window.remove(0);
+            }
+            window.push(byte[0]);
+            if window.len() == size && window[..4] == [0xff; 4] && window[4..] == *marker {
+                return Ok(Some(self.position - from - size as u64));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read + Seek> Read for Scan<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Verifier;
+    use crate::errors::Error;
+    use crate::tests::test_config;
+    use crate::writer::Writer;
+    use crate::{Codec, CompressionType, Text};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    fn write_file(compression: CompressionType) -> Vec<u8> {
+        let config = test_config()
+            .compression(compression, Codec::Default)
+            .block_size(512)
+            .sync_interval(512);
+        let mut writer = Writer::<_, i64, Text>::new(Vec::new(), config).unwrap();
+        for i in 0..1000 {
+            writer
+                .append(&i, &Text::from(format!("value {}", i)))
+                .unwrap();
+        }
+        writer.close().unwrap()
+    }
+
+    #[test]
+    fn verifies_reference_files() {
+        for name in &[
+            "abc_long_text_none",
+            "abc_long_text_deflate_record",
+            "abc_long_text_gzip_block",
+            "abc_long_text_bzip2_block",
+        ] {
+            let file = File::open(format!("test_data/{}.seq", name)).unwrap();
+            let report = Verifier::new().decode::<i64, Text>().verify(file).unwrap();
+
+            assert!(report.is_ok(), "{}: {:?}", name, report.problems);
+            assert_eq!(26, report.records);
+        }
+    }
+
+    #[test]
+    fn reports_problems_and_resyncs() {
+        for &compression in &[
+            CompressionType::None,
+            CompressionType::Record,
+            CompressionType::Block,
+        ] {
+            let clean = write_file(compression);
+            let report = Verifier::new()
+                .decode::<i64, Text>()
+                .verify(Cursor::new(&clean))
+                .unwrap();
+            assert!(report.is_ok());
+            assert_eq!(1000, report.records);
+            assert!(report.sync_markers > 1);
+
+            let mut corrupt = clean.clone();
+            let middle = corrupt.len() / 2;
+            for b in &mut corrupt[middle..middle + 8] {
+                *b = !*b;
+            }
+            let report = Verifier::new()
+                .decode::<i64, Text>()
+                .verify(Cursor::new(&corrupt))
+                .unwrap();
+
+            assert!(!report.is_ok(), "{:?}", compression);
+            let problem = &report.problems[0];
+            assert!(problem.offset < middle as u64 + 8, "{}", problem);
+            // records after the next sync marker are still read
+            assert!(
+                report.records > 500 && report.records < 1000,
+                "{:?}",
+                report
+            );
+            assert!(report.skipped_bytes > 0);
+        }
+    }
+
+    #[test]
+    fn continues_after_bad_record_contents() {
+        for &compression in &[
+            CompressionType::None,
+            CompressionType::Record,
+            CompressionType::Block,
+        ] {
+            let clean = write_file(compression);
+            let report = Verifier::new()
+                .check_records(|key, _| match key {
+                    [0, 0, 0, 0, 0, 0, 1, 244] => Err(Error::BadRecord("key 500".to_string())),
+                    _ => Ok(()),
+                })
+                .verify(Cursor::new(&clean))
+                .unwrap();
+
+            // only the bad record is missing, nothing is skipped to reach a sync marker
+            assert_eq!(1, report.problems.len(), "{:?}", compression);
+            assert_eq!(999, report.records);
+            assert_eq!(0, report.skipped_bytes);
+        }
+    }
+
+    #[test]
+    fn reports_undecodable_records() {
+        let clean = write_file(CompressionType::None);
+        let report = Verifier::new()
+            .decode::<i64, i64>()
+            .max_problems(3)
+            .verify(Cursor::new(&clean))
+            .unwrap();
+
+        assert_eq!(3, report.problems.len());
+        assert!(report.truncated);
+        match report.problems[0].error {
+            Error::Serialization(_) | Error::UnexpectedDecoder(_) => (),
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_truncated_files() {
+        let mut clean = write_file(CompressionType::Block);
+        let len = clean.len();
+        clean.truncate(len - 10);
+        let report = Verifier::new().verify(Cursor::new(&clean)).unwrap();
+
+        assert_eq!(1, report.problems.len());
+        assert!(report.problems[0].to_string().starts_with("offset "));
+    }
+}
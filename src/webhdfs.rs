@@ -0,0 +1,631 @@
+//! Reading and writing sequencefiles over WebHDFS, for tools outside the cluster with only
+//! HTTP access to the namenode. Files are read with `OPEN` requests of byte ranges, fetching
+//! the header first and each split with a single request, and written with a `CREATE`
+//! followed by `APPEND`s of buffered data.
+//!
+//! # Example
+//! ```no_run
+//! # use sequencefile::webhdfs::WebHdfs;
+//! # use sequencefile::{Text, WriterConfig};
+//! # fn main() -> sequencefile::Result<()> {
+//! # let config = WriterConfig::new("org.apache.hadoop.io.LongWritable", "org.apache.hadoop.io.Text");
+//! let hdfs = WebHdfs::new("http://namenode:9870").user("etl");
+//! for split in hdfs.splits("/data/events/part-r-00000", 64 * 1024 * 1024)? {
+//!     for kv in hdfs.open_split::<i64, Text>("/data/events/part-r-00000", split)? {
+//!         let (key, value) = kv?;
+//!     }
+//! }
+//!
+//! let mut writer = hdfs.create::<i64, Text>("/data/out/part-r-00000", config)?;
+//! writer.append(&1, &Text::from("one"))?;
+//! writer.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::ranged::{split_ranges, Fetch, RangedFile};
+use crate::reader::Reader;
+use crate::writable::Writable;
+use crate::writer::{Writer, WriterConfig};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::Value;
+use std::io::{self, Read};
+use std::ops::Range;
+use ureq::http::Response;
+use ureq::{Agent, Body, RequestBuilder};
+
+pub use crate::ranged::{DEFAULT_FETCH_SIZE, HEADER_FETCH_SIZE};
+
+// percent-encodes all but unreserved characters and `/` in paths
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Default number of bytes buffered by `WebHdfsOutput` before they are sent
+pub const DEFAULT_UPLOAD_SIZE: usize = 8 * 1024 * 1024;
+
+/// Client of the WebHDFS REST API of a namenode
+#[derive(Debug, Clone)]
+pub struct WebHdfs {
+    url: String,
+    user: Option<String>,
+    agent: Agent,
+}
+
+impl WebHdfs {
+    /// Client of the namenode at `url`, e.g. `http://namenode:9870`
+    pub fn new(url: &str) -> WebHdfs {
+        // datanode redirects are followed explicitly, to send data to them
+        let agent = Agent::config_builder()
+            .max_redirects(0)
+            .http_status_as_error(false)
+            .build()
+            .new_agent();
+        WebHdfs {
+            url: url.trim_end_matches('/').to_string(),
+            user: None,
+            agent,
+        }
+    }
+
+    /// Sets the `user.name` of requests, for clusters with simple authentication
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Length in bytes of the file at `path`, from `GETFILESTATUS`
+    ///
+    /// # Failures
+    /// Returns `Error::IO`, of kind `io::ErrorKind::NotFound` for a missing file, if the
+    /// request fails
+    pub fn len(&self, path: &str) -> Result<u64> {
+        let request = self.request(self.agent.get(&self.url(path)), "GETFILESTATUS");
+        let mut response = check(request.call().map_err(transport)?)?;
+        let status = response.body_mut().read_to_string().map_err(transport)?;
+        let len = serde_json::from_str::<Value>(&status)
+            .ok()
+            .and_then(|status| status["FileStatus"]["length"].as_u64());
+        match len {
+            Some(len) => Ok(len),
+            None => Err(Error::IO(io::Error::other(format!(
+                "no length in file status of {}: {}",
+                path, status
+            )))),
+        }
+    }
+
+    /// Byte ranges of `split_size` covering the file at `path`, to read with `open_split`
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if the request fails
+    pub fn splits(&self, path: &str, split_size: u64) -> Result<Vec<Range<u64>>> {
+        Ok(split_ranges(self.len(path)?, split_size))
+    }
+
+    /// Reader of the whole file at `path`
+    ///
+    /// # Failures
+    /// Returns an `Error` if a request fails or the header is malformed
+    pub fn open<K: Writable, V: Writable>(&self, path: &str) -> Result<Reader<WebHdfsFile, K, V>> {
+        Reader::new(WebHdfsFile::open(self, path)?)
+    }
+
+    /// Reader of the records of one split of the file at `path`, see `Reader::split`
+    ///
+    /// # Failures
+    /// Returns an `Error` if a request fails or the header is malformed
+    pub fn open_split<K: Writable, V: Writable>(
+        &self,
+        path: &str,
+        split: Range<u64>,
+    ) -> Result<Reader<WebHdfsFile, K, V>> {
+        let file = WebHdfsFile::open(self, path)?.split(split.clone());
+        let mut reader = Reader::new(file)?;
+        reader.split(split.start, split.end)?;
+        Ok(reader)
+    }
+
+    /// Writer of a new file at `path`, replacing any existing one. It is finished with
+    /// `Writer::finish`, which reports failed uploads that dropping it would ignore.
+    ///
+    /// # Failures
+    /// Returns an `Error` if the header cannot be written
+    pub fn create<K: Writable, V: Writable>(
+        &self,
+        path: &str,
+        config: WriterConfig,
+    ) -> Result<Writer<WebHdfsOutput, K, V>> {
+        Writer::new(WebHdfsOutput::create(self, path), config)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/webhdfs/v1{}", self.url, utf8_percent_encode(path, PATH))
+    }
+
+    // adds the `op` and `user.name` query parameters
+    fn request<B>(&self, request: RequestBuilder<B>, op: &str) -> RequestBuilder<B> {
+        let request = request.query("op", op);
+        match self.user {
+            Some(ref user) => request.query("user.name", user),
+            None => request,
+        }
+    }
+
+    // sends `data` to the datanode the namenode redirects a CREATE or APPEND to
+    fn upload(&self, path: &str, op: &str, data: &[u8]) -> Result<()> {
+        let url = self.url(path);
+        let redirect = if op == "CREATE" {
+            let request = self.request(self.agent.put(&url), op);
+            request.query("overwrite", "true").send_empty()
+        } else {
+            self.request(self.agent.post(&url), op).send_empty()
+        };
+        let location = redirect_location(check(redirect.map_err(transport)?)?)?;
+        let response = if op == "CREATE" {
+            self.agent.put(&location).send(data)
+        } else {
+            self.agent.post(&location).send(data)
+        };
+        check(response.map_err(transport)?)?;
+        Ok(())
+    }
+}
+
+/// Seekable reader of a file, buffering the last fetched range
+#[derive(Debug)]
+pub struct WebHdfsFile {
+    inner: RangedFile<Open>,
+}
+
+impl WebHdfsFile {
+    /// Reader of the file at `path`, its length is taken from `GETFILESTATUS`
+    ///
+    /// # Failures
+    /// Returns `Error::IO`, of kind `io::ErrorKind::NotFound` for a missing file, if the
+    /// request fails
+    pub fn open(client: &WebHdfs, path: &str) -> Result<WebHdfsFile> {
+        let len = client.len(path)?;
+        let open = Open {
+            client: client.clone(),
+            path: path.to_string(),
+        };
+        Ok(WebHdfsFile {
+            inner: RangedFile::new(open, len),
+        })
+    }
+
+    /// Sets the number of bytes fetched per `OPEN` outside of a split
+    pub fn fetch_size(mut self, fetch_size: u64) -> Self {
+        self.inner.fetch_size = fetch_size.max(1);
+        self
+    }
+
+    /// Fetches `split` with a single `OPEN` once it is reached, and only the header before it
+    pub fn split(mut self, split: Range<u64>) -> Self {
+        self.inner.split = Some(split);
+        self
+    }
+
+    /// Length of the file
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    /// Tells if the file is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl io::Read for WebHdfsFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl io::Seek for WebHdfsFile {
+    fn seek(&mut self, position: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+#[derive(Debug)]
+struct Open {
+    client: WebHdfs,
+    path: String,
+}
+
+impl Fetch for Open {
+    fn fetch(&mut self, range: Range<u64>) -> io::Result<Vec<u8>> {
+        let url = self.client.url(&self.path);
+        let request = self
+            .client
+            .request(self.client.agent.get(&url), "OPEN")
+            .query("offset", range.start.to_string())
+            .query("length", (range.end - range.start).to_string());
+        let fetch = |request: RequestBuilder<_>| match request.call() {
+            Ok(response) => check(response).map_err(into_io),
+            Err(e) => Err(e.into_io()),
+        };
+        let location = redirect_location(fetch(request)?).map_err(into_io)?;
+
+        let mut data = Vec::with_capacity((range.end - range.start) as usize);
+        fetch(self.client.agent.get(&location))?
+            .into_body()
+            .into_reader()
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Output to a file, sending buffered data with a `CREATE` and then `APPEND`s. Buffered data
+/// is sent when the buffer fills, on `flush` and `close`. Data stays buffered until it is
+/// sent, so a failed upload is retried by the next `flush` or `close`.
+///
+/// Drop only makes a last attempt, ignoring errors and not retrying a failed upload: it must
+/// not be relied on, end with `close` or `Writer::finish`.
+#[derive(Debug)]
+#[must_use = "buffered data is only reliably sent by `close`"]
+pub struct WebHdfsOutput {
+    client: WebHdfs,
+    path: String,
+    buffer: Vec<u8>,
+    upload_size: usize,
+    created: bool,
+    failed: bool,
+}
+
+impl WebHdfsOutput {
+    /// Output to a new file at `path`, replacing any existing one once data is sent
+    pub fn create(client: &WebHdfs, path: &str) -> WebHdfsOutput {
+        WebHdfsOutput {
+            client: client.clone(),
+            path: path.to_string(),
+            buffer: Vec::new(),
+            upload_size: DEFAULT_UPLOAD_SIZE,
+            created: false,
+            failed: false,
+        }
+    }
+
+    /// Output appending to the existing file at `path`
+    pub fn append(client: &WebHdfs, path: &str) -> WebHdfsOutput {
+        let mut output = WebHdfsOutput::create(client, path);
+        output.created = true;
+        output
+    }
+
+    /// Sets the number of bytes buffered before they are sent
+    pub fn upload_size(mut self, upload_size: usize) -> Self {
+        self.upload_size = upload_size.max(1);
+        self
+    }
+
+    /// Sends buffered data, creating the file if nothing was sent yet
+    ///
+    /// # Failures
+    /// Returns `Error::IO` if a request fails
+    pub fn close(mut self) -> Result<()> {
+        self.upload()
+    }
+
+    fn upload(&mut self) -> Result<()> {
+        if self.buffer.is_empty() && self.created {
+            return Ok(());
+        }
+        let op = if self.created { "APPEND" } else { "CREATE" };
+        let result = self.client.upload(&self.path, op, &self.buffer);
+        self.failed = result.is_err();
+        result?;
+        self.buffer.clear();
+        self.created = true;
+        Ok(())
+    }
+}
+
+impl<K: Writable, V: Writable> Writer<WebHdfsOutput, K, V> {
+    /// Closes the writer and its output, sending what is still buffered
+    ///
+    /// # Failures
+    /// Returns an `Error` if the writer cannot be closed or an upload fails
+    pub fn finish(self) -> Result<()> {
+        self.close()?.close()
+    }
+}
+
+impl io::Write for WebHdfsOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.buffer.len();
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= self.upload_size {
+            if let Err(e) = self.upload() {
+                // nothing of `buf` is written
+                self.buffer.truncate(len);
+                return Err(into_io(e));
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.upload().map_err(into_io)
+    }
+}
+
+impl Drop for WebHdfsOutput {
+    fn drop(&mut self) {
+        if !self.failed {
+            let _ = self.upload();
+        }
+    }
+}
+
+// fails with the RemoteException of an error response
+fn check(mut response: Response<Body>) -> Result<Response<Body>> {
+    let status = response.status().as_u16();
+    if status < 400 {
+        return Ok(response);
+    }
+    let kind = match status {
+        404 => io::ErrorKind::NotFound,
+        401 | 403 => io::ErrorKind::PermissionDenied,
+        _ => io::ErrorKind::Other,
+    };
+    let body = response.body_mut().read_to_string().unwrap_or_default();
+    Err(Error::IO(io::Error::new(
+        kind,
+        format!("WebHDFS status {}: {}", status, body.trim()),
+    )))
+}
+
+fn redirect_location(response: Response<Body>) -> Result<String> {
+    let location = response
+        .headers()
+        .get("location")
+        .and_then(|location| location.to_str().ok());
+    match location {
+        Some(location) if response.status().is_redirection() => Ok(location.to_string()),
+        _ => Err(Error::IO(io::Error::other(format!(
+            "expected a redirect to a datanode, got status {}",
+            response.status()
+        )))),
+    }
+}
+
+fn transport(e: ::ureq::Error) -> Error {
+    Error::IO(e.into_io())
+}
+
+fn into_io(e: Error) -> io::Error {
+    match e {
+        Error::IO(e) => e,
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WebHdfs, WebHdfsOutput};
+    use crate::errors::Error;
+    use crate::tests::test_config;
+    use crate::writer::Writer;
+    use crate::{Codec, CompressionType, Text};
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    // namenode and datanode endpoints of WebHDFS over one listener, logging the namenode
+    // requests as "op offset length"
+    fn mock_webhdfs() -> (String, Files, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let files = Files::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (server_url, server_files, server_log) = (url.clone(), files.clone(), log.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let _ = serve(stream.unwrap(), &server_url, &server_files, &server_log);
+            }
+        });
+        (url, files, log)
+    }
+
+    fn serve(
+        stream: TcpStream,
+        url: &str,
+        files: &Files,
+        log: &Mutex<Vec<String>>,
+    ) -> io::Result<()> {
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default().to_string();
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            input.read_line(&mut header)?;
+            if header.trim().is_empty() {
+                break;
+            }
+            let header = header.to_ascii_lowercase();
+            if let Some(len) = header.strip_prefix("content-length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        input.read_exact(&mut body)?;
+
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let params: HashMap<&str, &str> = query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .collect();
+        let param = |name: &str| params.get(name).map(|v| v.parse::<usize>().unwrap());
+        let op = params.get("op").cloned().unwrap_or_default();
+        let mut files = files.lock().unwrap();
+        let not_found = (
+            "404 Not Found",
+            String::new(),
+            br#"{"RemoteException":{"exception":"FileNotFoundException"}}"#.to_vec(),
+        );
+
+        let (status, location, data) = match path.strip_prefix("/datanode") {
+            None => {
+                assert_eq!(Some(&"hdfs"), params.get("user.name"));
+                log.lock().unwrap().push(format!(
+                    "{} {} {}",
+                    op,
+                    param("offset").unwrap_or(0),
+                    param("length").unwrap_or(0)
+                ));
+                match (method.as_str(), op, files.get(path)) {
+                    ("GET", "GETFILESTATUS", Some(file)) => (
+                        "200 OK",
+                        String::new(),
+                        format!(
+                            r#"{{"FileStatus":{{"length":{},"type":"FILE"}}}}"#,
+                            file.len()
+                        )
+                        .into_bytes(),
+                    ),
+                    ("GET", "OPEN", Some(_))
+                    | ("PUT", "CREATE", _)
+                    | ("POST", "APPEND", Some(_)) => (
+                        "307 Temporary Redirect",
+                        format!("{}/datanode{}", url, target),
+                        Vec::new(),
+                    ),
+                    _ => not_found,
+                }
+            }
+            Some(path) => match (method.as_str(), op) {
+                ("GET", "OPEN") => {
+                    let file = &files[path];
+                    let offset = param("offset").unwrap();
+                    let end = (offset + param("length").unwrap()).min(file.len());
+                    ("200 OK", String::new(), file[offset..end].to_vec())
+                }
+                ("PUT", "CREATE") => {
+                    files.insert(path.to_string(), body);
+                    ("201 Created", String::new(), Vec::new())
+                }
+                ("POST", "APPEND") => {
+                    files.get_mut(path).unwrap().extend(body);
+                    ("200 OK", String::new(), Vec::new())
+                }
+                _ => not_found,
+            },
+        };
+
+        let mut output = stream;
+        write!(
+            output,
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n",
+            status,
+            data.len()
+        )?;
+        if !location.is_empty() {
+            write!(output, "Location: {}\r\n", location)?;
+        }
+        write!(output, "Connection: close\r\n\r\n")?;
+        output.write_all(&data)
+    }
+
+    #[test]
+    fn writes_and_reads_splits() {
+        let (url, files, log) = mock_webhdfs();
+        let hdfs = WebHdfs::new(&url).user("hdfs");
+        let config = test_config()
+            .compression(CompressionType::Record, Codec::Snappy)
+            .sync_interval(512);
+        let output = WebHdfsOutput::create(&hdfs, "/user/hdfs/part 0").upload_size(8192);
+        let mut writer = Writer::<_, i64, Text>::new(output, config).unwrap();
+        for i in 0..3000 {
+            writer
+                .append(&i, &Text::from(format!("value-{}", i)))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let uploads = log.lock().unwrap().clone();
+        assert_eq!("CREATE 0 0", uploads[0]);
+        assert!(uploads.len() > 2, "{:?}", uploads);
+        assert!(uploads[1..].iter().all(|op| op == "APPEND 0 0"));
+        let len = files.lock().unwrap()["/webhdfs/v1/user/hdfs/part%200"].len() as u64;
+        assert_eq!(len, hdfs.len("/user/hdfs/part 0").unwrap());
+
+        let splits = hdfs.splits("/user/hdfs/part 0", 16384).unwrap();
+        assert!(splits.len() > 2);
+        let mut keys = Vec::new();
+        for split in &splits {
+            let reader = hdfs
+                .open_split::<i64, Text>("/user/hdfs/part 0", split.clone())
+                .unwrap();
+            keys.extend(reader.map(|kv| kv.unwrap().0));
+        }
+        assert_eq!((0..3000).collect::<Vec<_>>(), keys);
+
+        // the second split's header and split range, then past its end
+        let log = log.lock().unwrap();
+        let opens: Vec<_> = log.iter().filter(|op| op.starts_with("OPEN")).collect();
+        assert!(opens.contains(&&"OPEN 0 16384".to_string()));
+        assert!(opens.contains(&&"OPEN 16384 16384".to_string()));
+    }
+
+    #[test]
+    fn keeps_data_of_failed_uploads() {
+        let (url, files, log) = mock_webhdfs();
+        let hdfs = WebHdfs::new(&url).user("hdfs");
+        let requests = || log.lock().unwrap().len();
+
+        let mut output = WebHdfsOutput::append(&hdfs, "/late").upload_size(4);
+        output.write_all(b"ab").unwrap();
+        assert!(output.write(b"cd").is_err());
+        output.write_all(b"c").unwrap();
+        assert!(output.flush().is_err());
+        files
+            .lock()
+            .unwrap()
+            .insert("/webhdfs/v1/late".to_string(), b"01".to_vec());
+        output.close().unwrap();
+        assert_eq!(b"01abc", &files.lock().unwrap()["/webhdfs/v1/late"][..]);
+
+        // drop does not retry a failed upload
+        let mut output = WebHdfsOutput::append(&hdfs, "/missing");
+        output.write_all(b"abc").unwrap();
+        assert!(output.flush().is_err());
+        let sent = requests();
+        drop(output);
+        assert_eq!(sent, requests());
+
+        // failed uploads reach the caller of finish
+        let output = WebHdfsOutput::append(&hdfs, "/missing");
+        let mut writer = Writer::<_, i64, Text>::new(output, test_config()).unwrap();
+        writer.append(&1, &Text::from("one")).unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let (url, _, _) = mock_webhdfs();
+        let hdfs = WebHdfs::new(&url).user("hdfs");
+        match hdfs.open::<i64, Text>("/missing") {
+            Err(Error::IO(e)) => {
+                assert_eq!(io::ErrorKind::NotFound, e.kind());
+                assert!(e.to_string().contains("FileNotFoundException"), "{}", e);
+            }
+            other => panic!("expected a missing file, got {:?}", other.map(|_| ())),
+        }
+    }
+}
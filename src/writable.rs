@@ -1,14 +1,33 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use crate::util::{ZeroCompress, ZeroCompressWrite};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
 
-use crate::errors::Result;
+use crate::errors::{Error, Result};
+
+/// Derives `Writable` for structs, see the `sequencefile-derive` crate
+#[cfg(feature = "derive")]
+pub use sequencefile_derive::Writable;
 
 /// Basic trait mapping hadoop.io.Writable abstract class
-/// Keys and Values types should implement this type to provide automatic deserialization
+/// Keys and Values types should implement this type to provide automatic (de)serialization
 pub trait Writable {
     /// reads byte from buffer and converts to a concrete instance of Writable
     fn read(buf: &mut impl std::io::Read) -> Result<Self>
     where
         Self: Sized;
+
+    /// writes this instance to buffer, the inverse of `read`
+    ///
+    /// Defaults to an error so read-only implementations keep compiling.
+    fn write(&self, _buf: &mut impl std::io::Write) -> Result<()> {
+        Err(Error::Serialization(
+            "write not supported for this Writable".to_string(),
+        ))
+    }
 }
 
 impl Writable for Vec<u8> {
@@ -17,6 +36,11 @@ impl Writable for Vec<u8> {
         buf.read_to_end(&mut result)?;
         Ok(result)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        buf.write_all(self)?;
+        Ok(())
+    }
 }
 
 impl Writable for i64 {
@@ -26,6 +50,10 @@ impl Writable for i64 {
     {
         Ok(buf.read_i64::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_i64::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for u64 {
@@ -35,6 +63,10 @@ impl Writable for u64 {
     {
         Ok(buf.read_u64::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_u64::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for i32 {
@@ -44,6 +76,10 @@ impl Writable for i32 {
     {
         Ok(buf.read_i32::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_i32::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for u32 {
@@ -53,6 +89,10 @@ impl Writable for u32 {
     {
         Ok(buf.read_u32::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_u32::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for i16 {
@@ -62,6 +102,10 @@ impl Writable for i16 {
     {
         Ok(buf.read_i16::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_i16::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for u16 {
@@ -71,6 +115,10 @@ impl Writable for u16 {
     {
         Ok(buf.read_u16::<BigEndian>()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_u16::<BigEndian>(*self)?)
+    }
 }
 
 impl Writable for u8 {
@@ -80,6 +128,10 @@ impl Writable for u8 {
     {
         Ok(buf.read_u8()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_u8(*self)?)
+    }
 }
 
 impl Writable for i8 {
@@ -89,4 +141,378 @@ impl Writable for i8 {
     {
         Ok(buf.read_i8()?)
     }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(buf.write_i8(*self)?)
+    }
+}
+
+/// hadoop.io.BytesWritable, a byte array prefixed with its i32 length
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytesWritable(pub Vec<u8>);
+
+impl Writable for BytesWritable {
+    fn read(buf: &mut impl std::io::Read) -> Result<Self> {
+        let len = buf.read_i32::<BigEndian>()?;
+        if len < 0 {
+            return Err(Error::BadRecord(format!(
+                "negative BytesWritable length {}",
+                len
+            )));
+        }
+        let mut bytes = vec![0; len as usize];
+        buf.read_exact(&mut bytes)?;
+        Ok(BytesWritable(bytes))
+    }
+
+    fn write(&self, buf: &mut impl std::io::Write) -> Result<()> {
+        buf.write_i32::<BigEndian>(self.0.len() as i32)?;
+        buf.write_all(&self.0)?;
+        Ok(())
+    }
+}
+
+/// hadoop.io.NullWritable, a placeholder which is serialized as zero bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NullWritable;
+
+impl Writable for NullWritable {
+    fn read(_buf: &mut impl std::io::Read) -> Result<Self> {
+        Ok(NullWritable)
+    }
+
+    fn write(&self, _buf: &mut impl std::io::Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Integers which can be written in Hadoop's zero-compressed variable length encoding,
+/// see `WritableUtils.writeVInt`/`writeVLong`
+pub trait VarInt: Sized {
+    /// reads a variable length integer
+    fn read_vint(buf: &mut impl io::Read) -> Result<Self>;
+
+    /// writes a variable length integer
+    fn write_vint(&self, buf: &mut impl io::Write) -> Result<()>;
+}
+
+impl VarInt for i64 {
+    fn read_vint(buf: &mut impl io::Read) -> Result<Self> {
+        buf.decode_vint64()
+    }
+
+    fn write_vint(&self, buf: &mut impl io::Write) -> Result<()> {
+        buf.encode_vint64(*self)
+    }
+}
+
+impl VarInt for i32 {
+    fn read_vint(buf: &mut impl io::Read) -> Result<Self> {
+        let value = buf.decode_vint64()?;
+        i32::try_from(value).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("vint out of range: {}", value),
+            )
+            .into()
+        })
+    }
+
+    fn write_vint(&self, buf: &mut impl io::Write) -> Result<()> {
+        buf.encode_vint64(i64::from(*self))
+    }
+}
+
+/// Reads a collection written as an i32 element count followed by each element
+pub fn read_seq<R, T, C, F>(buf: &mut R, mut read_elem: F) -> Result<C>
+where
+    R: io::Read,
+    C: std::iter::FromIterator<T>,
+    F: FnMut(&mut R) -> Result<T>,
+{
+    let len = buf.read_i32::<BigEndian>()?;
+    (0..len).map(|_| read_elem(buf)).collect()
+}
+
+/// Writes a collection as an i32 element count followed by each element
+pub fn write_seq<'a, W, T, I, F>(buf: &mut W, items: I, mut write_elem: F) -> Result<()>
+where
+    W: io::Write,
+    T: 'a,
+    I: IntoIterator<Item = &'a T>,
+    I::IntoIter: ExactSizeIterator,
+    F: FnMut(&mut W, &T) -> Result<()>,
+{
+    let items = items.into_iter();
+    buf.write_i32::<BigEndian>(items.len() as i32)?;
+    for item in items {
+        write_elem(buf, item)?;
+    }
+    Ok(())
+}
+
+/// Reads a map written as an i32 entry count followed by alternating keys and values
+pub fn read_map<R, K, V, C, FK, FV>(buf: &mut R, mut read_key: FK, mut read_val: FV) -> Result<C>
+where
+    R: io::Read,
+    C: std::iter::FromIterator<(K, V)>,
+    FK: FnMut(&mut R) -> Result<K>,
+    FV: FnMut(&mut R) -> Result<V>,
+{
+    let len = buf.read_i32::<BigEndian>()?;
+    (0..len)
+        .map(|_| Ok((read_key(buf)?, read_val(buf)?)))
+        .collect()
+}
+
+/// Writes a map as an i32 entry count followed by alternating keys and values
+pub fn write_map<'a, W, K, V, I, FK, FV>(
+    buf: &mut W,
+    entries: I,
+    mut write_key: FK,
+    mut write_val: FV,
+) -> Result<()>
+where
+    W: io::Write,
+    K: 'a,
+    V: 'a,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    I::IntoIter: ExactSizeIterator,
+    FK: FnMut(&mut W, &K) -> Result<()>,
+    FV: FnMut(&mut W, &V) -> Result<()>,
+{
+    let entries = entries.into_iter();
+    buf.write_i32::<BigEndian>(entries.len() as i32)?;
+    for (k, v) in entries {
+        write_key(buf, k)?;
+        write_val(buf, v)?;
+    }
+    Ok(())
+}
+
+/// Compares keys in their serialized form, without deserializing them.
+/// Mirrors hadoop.io.RawComparator, the ordering must agree with the key type's `Ord`.
+pub trait RawComparator {
+    /// compares two serialized keys
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+}
+
+impl<F> RawComparator for F
+where
+    F: Fn(&[u8], &[u8]) -> Ordering,
+{
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Writable keys which have a raw comparator, like hadoop.io.WritableComparable
+/// paired with its registered WritableComparator
+pub trait WritableComparable: Writable + Ord {
+    /// comparator ordering serialized keys the same way `Ord` orders deserialized ones
+    type Comparator: RawComparator + Default;
+
+    /// a comparator instance for this key type
+    fn raw_comparator() -> Self::Comparator {
+        Self::Comparator::default()
+    }
+}
+
+/// Lexicographic comparison of unsigned bytes, like `WritableComparator.compareBytes`
+pub fn compare_bytes(a: &[u8], b: &[u8]) -> Ordering {
+    a.cmp(b)
+}
+
+/// Compares whole serialized keys byte by byte
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BytesComparator;
+
+impl RawComparator for BytesComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        compare_bytes(a, b)
+    }
+}
+
+/// Raw comparator for hadoop.io.Text, compares the utf8 bytes after the vint length
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TextComparator;
+
+impl RawComparator for TextComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        compare_bytes(skip_vint(a), skip_vint(b))
+    }
+}
+
+/// Raw comparator for hadoop.io.LongWritable, big-endian signed 64 bit integers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LongComparator;
+
+impl RawComparator for LongComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        if a.len() < 8 || b.len() < 8 {
+            return compare_bytes(a, b);
+        }
+        BigEndian::read_i64(a).cmp(&BigEndian::read_i64(b))
+    }
+}
+
+/// Raw comparator for hadoop.io.IntWritable, big-endian signed 32 bit integers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IntComparator;
+
+impl RawComparator for IntComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        if a.len() < 4 || b.len() < 4 {
+            return compare_bytes(a, b);
+        }
+        BigEndian::read_i32(a).cmp(&BigEndian::read_i32(b))
+    }
+}
+
+/// Raw comparator for hadoop.io.BytesWritable, compares the bytes after the i32 length
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BytesWritableComparator;
+
+impl RawComparator for BytesWritableComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        compare_bytes(a.get(4..).unwrap_or(a), b.get(4..).unwrap_or(b))
+    }
+}
+
+/// Fallback comparator which deserializes both keys and compares them with `Ord`,
+/// like the default hadoop.io.WritableComparator. Keys which fail to deserialize
+/// are compared bytewise.
+pub struct DeserializingComparator<K> {
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<K> Default for DeserializingComparator<K> {
+    fn default() -> Self {
+        DeserializingComparator { _key: PhantomData }
+    }
+}
+
+impl<K> Clone for DeserializingComparator<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for DeserializingComparator<K> {}
+
+impl<K> fmt::Debug for DeserializingComparator<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DeserializingComparator")
+    }
+}
+
+impl<K: Writable + Ord> RawComparator for DeserializingComparator<K> {
+    fn compare(&self, mut a: &[u8], mut b: &[u8]) -> Ordering {
+        let (raw_a, raw_b) = (a, b);
+        match (K::read(&mut a), K::read(&mut b)) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => compare_bytes(raw_a, raw_b),
+        }
+    }
+}
+
+impl WritableComparable for Vec<u8> {
+    type Comparator = BytesComparator;
+}
+
+impl WritableComparable for BytesWritable {
+    type Comparator = BytesWritableComparator;
+}
+
+impl WritableComparable for i64 {
+    type Comparator = LongComparator;
+}
+
+impl WritableComparable for i32 {
+    type Comparator = IntComparator;
+}
+
+fn skip_vint(buf: &[u8]) -> &[u8] {
+    let size = match buf.first() {
+        Some(&b) if (b as i8) >= -112 => 1,
+        Some(&b) if (b as i8) < -120 => (-119 - (b as i8) as i32) as usize,
+        Some(&b) => (-111 - (b as i8) as i32) as usize,
+        None => 0,
+    };
+    buf.get(size..).unwrap_or(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Text;
+
+    fn serialize<T: Writable>(value: &T) -> Vec<u8> {
+        let mut buf = vec![];
+        value.write(&mut buf).unwrap();
+        buf
+    }
+
+    fn assert_consistent<T: WritableComparable>(values: &[T]) {
+        let cmp = T::raw_comparator();
+        for a in values {
+            for b in values {
+                assert_eq!(a.cmp(b), cmp.compare(&serialize(a), &serialize(b)));
+            }
+        }
+    }
+
+    #[test]
+    fn raw_comparators_agree_with_ord() {
+        assert_consistent(&[i64::MIN, -1, 0, 1, 255, 256, i64::MAX]);
+        assert_consistent(&[i32::MIN, -1, 0, 1, i32::MAX]);
+        assert_consistent(&[vec![], vec![0u8], vec![1], vec![0, 255], vec![255]]);
+        assert_consistent(&[
+            BytesWritable(vec![]),
+            BytesWritable(vec![1]),
+            BytesWritable(vec![0, 1]),
+            BytesWritable(vec![0; 300]),
+            BytesWritable(vec![255]),
+        ]);
+        let long = "z".repeat(200);
+        assert_consistent(&[
+            Text::from(""),
+            Text::from("a"),
+            Text::from("ab"),
+            Text::from("b"),
+            Text::from(long.as_str()),
+            Text::from("é"),
+        ]);
+    }
+
+    #[test]
+    fn bytes_writable_rejects_negative_length() {
+        let mut buf = io::Cursor::new(serialize(&-1i32));
+
+        match BytesWritable::read(&mut buf) {
+            Err(Error::BadRecord(_)) => {}
+            other => panic!("expected BadRecord, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializing_comparator_orders_by_value() {
+        let cmp = DeserializingComparator::<i16>::default();
+
+        assert_eq!(
+            Ordering::Less,
+            cmp.compare(&serialize(&-5i16), &serialize(&3i16))
+        );
+        assert_eq!(
+            Ordering::Less,
+            compare_bytes(&serialize(&3i16), &serialize(&-5i16))
+        );
+    }
+
+    #[test]
+    fn closures_are_comparators() {
+        let reversed = |a: &[u8], b: &[u8]| b.cmp(a);
+
+        assert_eq!(Ordering::Greater, reversed.compare(&[1], &[2]));
+    }
 }
@@ -0,0 +1,567 @@
+//! Implementation and structs for a sequencefile writer
+
+use crate::compress;
+use crate::compress::{Codec, CompressionType};
+use crate::errors::{Error, Result};
+use crate::reader::{Reader, MAGIC, SYNC_SIZE};
+use crate::text::write_text;
+use crate::util::ZeroCompressWrite;
+use byteorder::{BigEndian, WriteBytesExt};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufWriter, SeekFrom};
+use std::marker::PhantomData;
+use std::mem;
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::writable::Writable;
+use crate::Header;
+
+const VERSION: u8 = 6;
+const SYNC_ESCAPE: i32 = -1;
+
+/// Default uncompressed size of a block before it is compressed and written,
+/// matches Hadoop's `io.seqfile.compress.blocksize`
+pub const DEFAULT_BLOCK_SIZE: usize = 1_000_000;
+
+/// Default number of bytes between sync markers in record and uncompressed files
+pub const DEFAULT_SYNC_INTERVAL: u64 = 5 * 1024 * SYNC_SIZE as u64;
+
+/// Settings for a new sequencefile: key/value classes, compression and metadata
+#[derive(Debug, Clone)]
+pub struct WriterConfig {
+    /// Fully-qualified Java class of key Writable
+    pub key_class: String,
+
+    /// Fully-qualified Java class of value Writable
+    pub value_class: String,
+
+    /// Type of value compression
+    pub compression_type: CompressionType,
+
+    /// Codec, if any
+    pub compression_codec: Option<Codec>,
+
+    /// K-V metadata on sequencefile
+    pub metadata: HashMap<String, String>,
+
+    /// Uncompressed bytes buffered per block with `CompressionType::Block`
+    pub block_size: usize,
+
+    /// Minimum bytes between sync markers with `CompressionType::None` and `Record`
+    pub sync_interval: u64,
+
+    /// Whether `Writer::append_if_exists` truncates a torn record or block at the end of
+    /// the file instead of failing
+    pub truncate_torn_tail: bool,
+}
+
+impl WriterConfig {
+    /// Uncompressed sequencefile settings for the given Java key and value classes
+    pub fn new(key_class: &str, value_class: &str) -> WriterConfig {
+        WriterConfig {
+            key_class: key_class.to_string(),
+            value_class: value_class.to_string(),
+            compression_type: CompressionType::None,
+            compression_codec: None,
+            metadata: HashMap::new(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+            truncate_torn_tail: false,
+        }
+    }
+
+    /// Same classes, compression and metadata as an existing file
+    pub fn from_header(header: &Header) -> WriterConfig {
+        WriterConfig {
+            key_class: header.key_class.clone(),
+            value_class: header.value_class.clone(),
+            compression_type: header.compression_type,
+            compression_codec: header.compression_codec,
+            metadata: header.metadata.clone(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+            truncate_torn_tail: false,
+        }
+    }
+
+    /// Sets the compression type and codec, the codec is ignored for `CompressionType::None`
+    pub fn compression(mut self, compression_type: CompressionType, codec: Codec) -> Self {
+        self.compression_type = compression_type;
+        self.compression_codec = match compression_type {
+            CompressionType::None => None,
+            _ => Some(codec),
+        };
+        self
+    }
+
+    /// Adds a metadata entry
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the uncompressed block size
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets the sync marker interval
+    pub fn sync_interval(mut self, sync_interval: u64) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    /// Sets whether `Writer::append_if_exists` truncates a torn end of the file
+    pub fn truncate_torn_tail(mut self, truncate_torn_tail: bool) -> Self {
+        self.truncate_torn_tail = truncate_torn_tail;
+        self
+    }
+}
+
+/// Writes key-value pairs to a sequencefile.
+/// Only buffers when `CompressionType::Block` is used, blocks are written when they
+/// reach the configured block size and on `sync`, `hflush`, `hsync` and `close`.
+#[derive(Debug)]
+pub struct Writer<W: io::Write, K: Writable, V: Writable> {
+    /// Sequencefile header
+    pub header: Header,
+    out: Option<Output<W>>,
+    last_sync: u64,
+    block_size: usize,
+    sync_interval: u64,
+    block: Block,
+    key_buf: Vec<u8>,
+    value_buf: Vec<u8>,
+    _kv: PhantomData<fn(&K, &V)>,
+}
+
+impl<W: io::Write, K: Writable, V: Writable> Writer<W, K, V> {
+    /// Create a new Writer over an io::Write, writing the header immediately
+    ///
+    /// # Failures
+    /// Returns an `Error` if the header cannot be written
+    pub fn new(w: W, config: WriterConfig) -> Result<Writer<W, K, V>> {
+        let compression_codec = match config.compression_type {
+            CompressionType::None => None,
+            _ => Some(config.compression_codec.unwrap_or(Codec::Default)),
+        };
+
+        let header = Header {
+            version: VERSION as u16,
+            compression_type: config.compression_type,
+            compression_codec,
+            key_class: config.key_class,
+            value_class: config.value_class,
+            metadata: config.metadata,
+            sync_marker: generate_sync_marker(),
+        };
+
+        let mut out = Output {
+            inner: BufWriter::new(w),
+            position: 0,
+        };
+        write_header(&mut out, &header)?;
+
+        Ok(Writer {
+            header,
+            last_sync: 0,
+            out: Some(out),
+            block_size: config.block_size,
+            sync_interval: config.sync_interval,
+            block: Block::default(),
+            key_buf: Vec::new(),
+            value_buf: Vec::new(),
+            _kv: PhantomData,
+        })
+    }
+
+    /// Serializes and appends a key-value pair
+    pub fn append(&mut self, key: &K, value: &V) -> Result<()> {
+        let mut key_buf = mem::take(&mut self.key_buf);
+        let mut value_buf = mem::take(&mut self.value_buf);
+        key_buf.clear();
+        value_buf.clear();
+
+        let result = key
+            .write(&mut key_buf)
+            .and_then(|_| value.write(&mut value_buf))
+            .and_then(|_| self.append_raw(&key_buf, &value_buf));
+
+        self.key_buf = key_buf;
+        self.value_buf = value_buf;
+        result
+    }
+
+    /// Appends an already serialized key-value pair, the value must be uncompressed
+    pub fn append_raw(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        match self.header.compression_type {
+            CompressionType::Block => {
+                self.block.push(key, value)?;
+                if self.block.size() >= self.block_size {
+                    self.write_block()?;
+                }
+                Ok(())
+            }
+            CompressionType::Record => {
+                let codec = self.header.compression_codec.unwrap_or(Codec::Default);
+                let value = compress::compressor(&codec, value)?;
+                self.write_record(key, &value)
+            }
+            CompressionType::None => self.write_record(key, value),
+        }
+    }
+
+    /// Bytes written so far, a buffered block only counts once it is compressed and written
+    pub fn position(&self) -> u64 {
+        self.out.as_ref().map_or(0, |out| out.position)
+    }
+
+    /// Writes any buffered block and flushes, returning the underlying io::Write
+    ///
+    /// A returned `AtomicFile` is not committed yet: use `Writer::commit`, or an
+    /// `AtomicWriter` which commits on `close`.
+    pub fn close(mut self) -> Result<W> {
+        self.hflush()?;
+        let out = self.out.take().expect("writer already closed");
+        out.inner.into_inner().map_err(|e| e.into_error().into())
+    }
+
+    /// Writes any buffered block and a sync marker, unless the output already ends with one,
+    /// so readers can start from the current position, like Hadoop's `Writer.sync`
+    ///
+    /// # Failures
+    /// Returns an `Error` if the block or marker cannot be written
+    pub fn sync(&mut self) -> Result<()> {
+        if self.header.compression_type == CompressionType::Block {
+            self.write_block()?;
+        }
+        self.write_sync()
+    }
+
+    /// Writes any buffered block and flushes everything written to the underlying io::Write,
+    /// like Hadoop's `Writer.hflush`
+    ///
+    /// # Failures
+    /// Returns an `Error` if the block cannot be written or the output flushed
+    pub fn hflush(&mut self) -> Result<()> {
+        if self.header.compression_type == CompressionType::Block {
+            self.write_block()?;
+        }
+        self.output().flush()?;
+        Ok(())
+    }
+
+    fn output(&mut self) -> &mut Output<W> {
+        self.out.as_mut().expect("writer already closed")
+    }
+
+    fn write_record(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if self.position() >= self.last_sync + self.sync_interval {
+            self.write_sync()?;
+        }
+
+        let out = self.output();
+        out.write_i32::<BigEndian>((key.len() + value.len()) as i32)?;
+        out.write_i32::<BigEndian>(key.len() as i32)?;
+        out.write_all(key)?;
+        out.write_all(value)?;
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> Result<()> {
+        if self.block.records == 0 {
+            return Ok(());
+        }
+
+        self.write_sync()?;
+
+        let codec = self.header.compression_codec.unwrap_or(Codec::Default);
+        let block = mem::take(&mut self.block);
+        let out = self.output();
+        out.encode_vint64(block.records as i64)?;
+        for section in &[
+            &block.key_lengths,
+            &block.keys,
+            &block.value_lengths,
+            &block.values,
+        ] {
+            let compressed = compress::compressor(&codec, section)?;
+            out.encode_vint64(compressed.len() as i64)?;
+            out.write_all(&compressed)?;
+        }
+        Ok(())
+    }
+
+    fn write_sync(&mut self) -> Result<()> {
+        if self.last_sync == self.position() {
+            return Ok(());
+        }
+
+        let sync_marker = self.header.sync_marker.clone();
+        let out = self.output();
+        out.write_i32::<BigEndian>(SYNC_ESCAPE)?;
+        out.write_all(&sync_marker)?;
+        self.last_sync = self.position();
+        Ok(())
+    }
+}
+
+impl<W: Syncable, K: Writable, V: Writable> Writer<W, K, V> {
+    /// Writes any buffered block, flushes and persists the output to durable storage, like
+    /// Hadoop's `Writer.hsync`: a crash loses at most the records appended afterwards
+    ///
+    /// # Failures
+    /// Returns an `Error` if the block cannot be written or the output flushed or persisted
+    pub fn hsync(&mut self) -> Result<()> {
+        self.hflush()?;
+        self.output().inner.get_mut().hsync()?;
+        Ok(())
+    }
+}
+
+impl<K: Writable, V: Writable> Writer<File, K, V> {
+    /// Appends to the file at `path` if it exists, like Hadoop's
+    /// `SequenceFile.Writer.appendIfExists`, otherwise creates it with `config`. An existing
+    /// file keeps its header and sync marker, its metadata wins over `config`'s. Appended
+    /// records start after a sync marker.
+    ///
+    /// A torn record or block left at the end by a writer which did not close is an error,
+    /// unless `WriterConfig::truncate_torn_tail` is set: it is then truncated.
+    ///
+    /// # Failures
+    /// Returns `Error::IncompatibleHeader` if the file's classes, compression type or codec
+    /// differ from `config`, `Error::BadRecord` if the file ends with a torn record or
+    /// block, or an `Error` if it is not a readable sequencefile
+    pub fn append_if_exists<P: AsRef<Path>>(
+        path: P,
+        config: WriterConfig,
+    ) -> Result<Writer<File, K, V>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Writer::new(File::create(path)?, config);
+        }
+
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len();
+        let mut reader = Reader::<_, Vec<u8>, Vec<u8>>::new(&file)?;
+        check_appendable(&reader.header, &config)?;
+        let header = reader.header.clone();
+
+        // only the records after the last sync marker need to be checked for a torn end
+        reader.sync(0)?;
+        let header_len = reader.position();
+        let last_sync = find_last_sync(&file, header_len, len, &header.sync_marker)?;
+        reader.sync(last_sync)?;
+        let mut end = reader.position();
+        while let Some(Ok(_)) = reader.next() {
+            end = reader.position();
+        }
+        drop(reader);
+
+        // a final sync marker is the only tail that isn't torn
+        let mut ends_with_sync = false;
+        if len - end == (4 + SYNC_SIZE) as u64 {
+            let mut tail = [0; 4 + SYNC_SIZE];
+            (&file).seek(SeekFrom::Start(end))?;
+            (&file).read_exact(&mut tail)?;
+            ends_with_sync = tail[..4] == [0xff; 4] && tail[4..] == header.sync_marker[..];
+        }
+        if ends_with_sync {
+            end = len;
+        } else if end < len {
+            if !config.truncate_torn_tail {
+                return Err(Error::BadRecord(format!(
+                    "{} bytes at offset {} are not a complete record or block",
+                    len - end,
+                    end
+                )));
+            }
+            file.set_len(end)?;
+        }
+        (&file).seek(SeekFrom::Start(end))?;
+
+        let mut writer = Writer {
+            header,
+            last_sync: if ends_with_sync { end } else { 0 },
+            out: Some(Output {
+                inner: BufWriter::new(file),
+                position: end,
+            }),
+            block_size: config.block_size,
+            sync_interval: config.sync_interval,
+            block: Block::default(),
+            key_buf: Vec::new(),
+            value_buf: Vec::new(),
+            _kv: PhantomData,
+        };
+        writer.write_sync()?;
+        Ok(writer)
+    }
+}
+
+impl<W: io::Write, K: Writable, V: Writable> Drop for Writer<W, K, V> {
+    fn drop(&mut self) {
+        if self.out.is_some() {
+            let _ = self.hflush();
+        }
+    }
+}
+
+/// Output which can persist what was written to durable storage, like Hadoop's `Syncable`
+pub trait Syncable: io::Write {
+    /// Persists the bytes written so far, e.g. with `fsync`
+    fn hsync(&mut self) -> io::Result<()>;
+}
+
+impl Syncable for File {
+    fn hsync(&mut self) -> io::Result<()> {
+        self.sync_data()
+    }
+}
+
+#[derive(Debug)]
+struct Output<W: io::Write> {
+    inner: BufWriter<W>,
+    position: u64,
+}
+
+impl<W: io::Write> io::Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Block {
+    records: usize,
+    key_lengths: Vec<u8>,
+    keys: Vec<u8>,
+    value_lengths: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl Block {
+    fn push(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.key_lengths.encode_vint64(key.len() as i64)?;
+        self.keys.extend_from_slice(key);
+        self.value_lengths.encode_vint64(value.len() as i64)?;
+        self.values.extend_from_slice(value);
+        self.records += 1;
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.keys.len() + self.values.len()
+    }
+}
+
+fn write_header<W: io::Write>(out: &mut W, header: &Header) -> Result<()> {
+    out.write_all(MAGIC.as_bytes())?;
+    out.write_u8(header.version as u8)?;
+    write_text(out, &header.key_class)?;
+    write_text(out, &header.value_class)?;
+
+    let (compressed, block) = match header.compression_type {
+        CompressionType::None => (0, 0),
+        CompressionType::Record => (1, 0),
+        CompressionType::Block => (1, 1),
+    };
+    out.write_u8(compressed)?;
+    out.write_u8(block)?;
+    if let Some(ref codec) = header.compression_codec {
+        write_text(out, compress::codec_class(codec))?;
+    }
+
+    // sorted, as Hadoop keeps metadata in a TreeMap
+    let mut metadata: Vec<_> = header.metadata.iter().collect();
+    metadata.sort();
+    out.write_u32::<BigEndian>(metadata.len() as u32)?;
+    for (key, value) in metadata {
+        write_text(out, key)?;
+        write_text(out, value)?;
+    }
+
+    out.write_all(&header.sync_marker)?;
+    Ok(())
+}
+
+fn check_appendable(header: &Header, config: &WriterConfig) -> Result<()> {
+    let codec = match config.compression_type {
+        CompressionType::None => None,
+        _ => Some(config.compression_codec.unwrap_or(Codec::Default)),
+    };
+    if header.key_class != config.key_class
+        || header.value_class != config.value_class
+        || header.compression_type != config.compression_type
+        || header.compression_codec != codec
+    {
+        return Err(Error::IncompatibleHeader(format!(
+            "cannot append {}/{} with {:?} compression and {:?} to {}/{} with {:?} and {:?}",
+            config.key_class,
+            config.value_class,
+            config.compression_type,
+            codec,
+            header.key_class,
+            header.value_class,
+            header.compression_type,
+            header.compression_codec
+        )));
+    }
+    Ok(())
+}
+
+// offset of the last sync escape and marker after the header, or the header's end
+fn find_last_sync(file: &File, header_len: u64, len: u64, marker: &[u8]) -> Result<u64> {
+    const CHUNK: u64 = 64 * 1024;
+
+    let mut pattern = vec![0xff; 4];
+    pattern.extend_from_slice(marker);
+    let mut end = len;
+    while end > header_len {
+        let start = end.saturating_sub(CHUNK).max(header_len);
+        // overlaps the previous chunk by a pattern length less one
+        let mut chunk = vec![0; (len.min(end + pattern.len() as u64 - 1) - start) as usize];
+        let mut file = file;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        if let Some(i) = chunk
+            .windows(pattern.len())
+            .rposition(|w| w == &pattern[..])
+        {
+            return Ok(start + i as u64);
+        }
+        end = start;
+    }
+    Ok(header_len)
+}
+
+fn generate_sync_marker() -> Vec<u8> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let mut marker = Vec::with_capacity(SYNC_SIZE);
+    while marker.len() < SYNC_SIZE {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u32(process::id());
+        hasher.write_usize(marker.len());
+        marker.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+    marker
+}
@@ -0,0 +1,197 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+fn seqfile(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_header() {
+    let header = seqfile(&["header", "test_data/abc_long_text_gzip_block.seq"]);
+
+    assert!(header.contains("key class: org.apache.hadoop.io.LongWritable\n"));
+    assert!(header.contains("value class: org.apache.hadoop.io.Text\n"));
+    assert!(header.contains("compression: block\n"));
+    assert!(header.contains("codec: org.apache.hadoop.io.compress.GzipCodec\n"));
+}
+
+#[test]
+fn prints_records() {
+    let head = seqfile(&["head", "-n", "2", "test_data/abc_long_text_none.seq"]);
+    assert_eq!("0\t\0A\n1\t\0B\n", head);
+
+    let text = seqfile(&["text", "test_data/abc_long_text_deflate_record.seq"]);
+    assert_eq!(26, text.lines().count());
+    assert_eq!(Some("25\t\0Z"), text.lines().last());
+
+    let count = seqfile(&["count", "test_data/complex.seq"]);
+    assert_eq!("1000\n", count);
+}
+
+#[test]
+fn prints_stats() {
+    let stat = seqfile(&["stat", "test_data/abc_long_text_bzip2_block.seq"]);
+
+    assert!(stat.contains("records: 26\n"));
+    assert!(stat.contains("blocks: 1\n"));
+    assert!(stat.contains("file size: 379\n"));
+}
+
+#[test]
+fn fails_on_bad_files() {
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["count", "test_data/bad_magic.seq"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("seqfile: bad or missing magic"));
+}
+
+#[test]
+fn verifies_files() {
+    let report = seqfile(&[
+        "verify",
+        "--decode",
+        "test_data/abc_long_text_gzip_block.seq",
+    ]);
+    assert!(report.contains("records: 26\n"));
+    assert!(report.ends_with("problems: 0\n"));
+
+    let mut corrupt = std::fs::read("test_data/abc_long_text_none.seq").unwrap();
+    corrupt[200] ^= 0xff;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("corrupt.seq");
+    std::fs::write(&path, corrupt).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["verify".as_ref(), path.as_os_str()])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.starts_with("offset "), "{}", report);
+    assert!(report.ends_with("problems: 1\n"), "{}", report);
+}
+
+#[test]
+fn converts_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("converted.seq");
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["convert", "--compression", "block", "--codec", "snappy"])
+        .args(["--metadata", "source=bzip2"])
+        .arg("test_data/abc_long_text_bzip2_record.seq")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let path = path.to_str().unwrap();
+    let header = seqfile(&["header", path]);
+    assert!(header.contains("compression: block\n"));
+    assert!(header.contains("codec: org.apache.hadoop.io.compress.SnappyCodec\n"));
+    assert!(header.contains("  source: bzip2\n"));
+    assert_eq!(
+        seqfile(&["text", "test_data/abc_long_text_bzip2_record.seq"]),
+        seqfile(&["text", path])
+    );
+
+    // a codec alone would be ignored
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["convert", "--codec", "snappy"])
+        .arg("test_data/abc_long_text_bzip2_record.seq")
+        .arg(path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--compression"));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn converts_to_parquet() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("converted.parquet");
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args([
+            "parquet",
+            "--compression",
+            "zstd(3)",
+            "--row-group-size",
+            "5",
+        ])
+        .arg("test_data/abc_long_text_gzip_record.seq")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let written = std::fs::read(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(b"PAR1", &written[..4]);
+    assert_eq!(b"PAR1", &written[written.len() - 4..]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["parquet", "--compression", "lzma"])
+        .arg("test_data/abc_long_text_gzip_record.seq")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn prints_json_lines() {
+    let json = seqfile(&[
+        "head",
+        "-n",
+        "2",
+        "--format",
+        "json",
+        "test_data/abc_long_text_snappy_block.seq",
+    ]);
+    assert_eq!(
+        "{\"key\":0,\"value\":\"\\u0000A\"}\n{\"key\":1,\"value\":\"\\u0000B\"}\n",
+        json
+    );
+
+    let json = seqfile(&[
+        "cat",
+        "--format",
+        "json",
+        "--bytes",
+        "hex",
+        "test_data/complex.seq",
+    ]);
+    assert_eq!(1000, json.lines().count());
+    assert!(json.lines().all(|l| l.starts_with("{\"key\":\"")));
+}
+
+#[test]
+fn imports_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("users.csv");
+    let path = dir.path().join("users.seq");
+    std::fs::write(&csv, "id,name\n1,alice\n2,\"bob, jr\"\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_seqfile"))
+        .args(["import", "--format", "csv", "--compression", "record"])
+        .args(["--key", "id:LongWritable", "--value", "name:Text"])
+        .arg(&csv)
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let path = path.to_str().unwrap();
+    assert!(seqfile(&["header", path]).contains("compression: record\n"));
+    assert_eq!(
+        "{\"key\":1,\"value\":\"alice\"}\n{\"key\":2,\"value\":\"bob, jr\"}\n",
+        seqfile(&["cat", "--format", "json", path])
+    );
+}